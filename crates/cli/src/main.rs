@@ -3,7 +3,8 @@ use clap::{Parser, Subcommand};
 use comfy_table::{Table, presets::UTF8_FULL, ContentArrangement};
 use humansize::{format_size, BINARY};
 use tracing::Level;
-use sentinel_core::{mem, procinfo, policy::{self, PressureState}, psi::PSIMetrics};
+use sentinel_core::{cgroups::{self, Hierarchy, ProtectedUnits, SliceClasses}, exclude::Exclusions, mem::{self, MemBreakdown, MemInfo}, procinfo, policy::{self, PressureState}, psi::PSIMetrics, rules::RuleSet, trend::Trend, zram::CompressedSwap};
+use sentinel_core::control::{self, DaemonStatus, Decision, Request, Response};
 use std::io::{self, Write};
 use serde::Serialize;
 
@@ -27,6 +28,8 @@ enum Commands {
         json: bool,
         #[arg(long)]
         watch: bool,
+        #[arg(long, help = "Show where memory is going (anon, file cache, shmem, slab, kernel)")]
+        breakdown: bool,
     },
     Top {
         #[arg(long, default_value_t = 10)]
@@ -42,7 +45,7 @@ enum Commands {
         #[arg(long)]
         explain: bool,
     },
-    Config { #[arg(value_parser=["get","set","init"])] op: String, key: Option<String>, value: Option<String> },
+    Config { #[arg(value_parser=["get","set","init","reload","check"])] op: String, key: Option<String>, value: Option<String> },
     Logs { #[arg(long)] since: Option<String>, #[arg(long)] follow: bool },
     Reserve { #[arg(value_parser=["hold","release","rebuild"])] op: String },
     Slices { #[arg(long)] tree: bool },
@@ -53,7 +56,7 @@ fn main() {
     let result = std::panic::catch_unwind(|| {
         let cli = Cli::parse();
         match cli.cmd {
-            Commands::Status { json, watch } => status(cli.unicode, json, watch),
+            Commands::Status { json, watch, breakdown } => status(cli.unicode, json, watch, breakdown),
             Commands::Top { limit, json } => top(limit, cli.unicode, json),
             Commands::Simulate { level, dry_run, explain } => simulate(&level, dry_run, explain),
             Commands::Config { op, key, value } => config_cmd(&op, key, value),
@@ -88,7 +91,11 @@ fn config_cmd(op: &str, key: Option<String>, value: Option<String>) -> Result<()
             init_config_interactive()?;
         }
         "get" => {
-            let cfg = Config::load_from(&cfg_path)?;
+            // Prefer what the running daemon actually has loaded
+            let cfg = match control::request(Request::Config) {
+                Ok(Response::Config(cfg)) => *cfg,
+                _ => Config::load_from(&cfg_path)?,
+            };
             if let Some(k) = key {
                 let val = match k.as_str() {
                     "reserve_mb" => cfg.reserve_mb.to_string(),
@@ -108,6 +115,22 @@ fn config_cmd(op: &str, key: Option<String>, value: Option<String>) -> Result<()
         "set" => {
             println!("Config set not yet implemented");
         }
+        "check" => {
+            let cfg = Config::load_from(&cfg_path)?;
+            let warnings = cfg.warnings();
+            for w in &warnings {
+                println!("warning: {}", w);
+            }
+            println!("{}: OK{}", cfg_path.display(),
+                     if warnings.is_empty() { String::new() } else { format!(" ({} warnings)", warnings.len()) });
+        }
+        "reload" => {
+            match daemon_request(Request::Reload)? {
+                Response::Reloaded { path: Some(path) } => println!("Daemon reloaded {}", path),
+                Response::Reloaded { path: None } => println!("Daemon reloaded its config"),
+                other => return Err(unexpected(other)),
+            }
+        }
         _ => println!("Unknown config op: {}", op),
     }
     Ok(())
@@ -121,38 +144,236 @@ fn logs_cmd(since: Option<String>, follow: bool) -> Result<()> {
 }
 
 fn reserve_cmd(op: &str) -> Result<()> {
-    use sentinel_core::reserve;
-    match op {
-        "hold" => {
-            reserve::hold(512);
-            println!("Reserve held");
-        }
-        "release" => {
-            reserve::release();
-            println!("Reserve released");
+    let (req, verb) = match op {
+        "hold" => (Request::ReserveHold, "held"),
+        "release" => (Request::ReserveRelease, "released"),
+        "rebuild" => (Request::ReserveRebuild, "rebuilt"),
+        _ => {
+            println!("Unknown reserve op: {}", op);
+            return Ok(());
         }
-        "rebuild" => {
-            reserve::release();
-            reserve::hold(512);
-            println!("Reserve rebuilt");
+    };
+    match daemon_request(req)? {
+        Response::Reserve { held, size_mb, resident_bytes, chunks_held, chunks_total } => {
+            println!("Reserve {} ({} MB configured, currently {}, {} resident)",
+                     verb, size_mb, reserve_state(held, chunks_held, chunks_total), format_size(resident_bytes, BINARY));
         }
-        _ => println!("Unknown reserve op: {}", op),
+        other => return Err(unexpected(other)),
     }
     Ok(())
 }
 
+const TREND_SAMPLE_SECS: u64 = 2;
+
+/* The daemon keeps a long history; without it take a quick local one, with a
+ * window sized so the samples cover the span a prediction needs */
+fn sample_trend() -> Result<Trend> {
+    let mut trend = Trend::new(std::time::Duration::from_secs(TREND_SAMPLE_SECS * 2));
+    let steps = 5;
+    for i in 0..steps {
+        if i > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(TREND_SAMPLE_SECS * 1000 / (steps - 1)));
+        }
+        let m = mem::sample()?;
+        trend.push(m.available() + CompressedSwap::sample().effective_swap_free_kb(&m));
+    }
+    Ok(trend)
+}
+
+fn format_trend(rate_kb_s: Option<f64>, exhaustion_secs: Option<u64>) -> String {
+    let Some(rate) = rate_kb_s else { return "not enough samples yet".into() };
+    let speed = format_size((rate.abs() * 1024.0) as u64, BINARY);
+    match exhaustion_secs {
+        Some(secs) => format!("headroom falling {}/s, exhaustion in ~{}s", speed, secs),
+        // Below 1 MiB/s either way is just noise
+        None if rate > 1024.0 => format!("headroom growing {}/s", speed),
+        None => "stable".into(),
+    }
+}
+
+/* What the daemon would do, rung by rung, if `state` persisted */
+fn print_ladder_plan(cfg: &sentinel_core::config::Config, state: PressureState, victim: Option<&procinfo::ProcWithBadness>) {
+    use sentinel_core::ladder::{self, Ladder, LadderAction};
+
+    println!("\n=== Escalation Ladder ({}, mode {}) ===",
+             if cfg.ladder.is_empty() { "default" } else { "configured" }, cfg.mode);
+    let hierarchy = cgroups::hierarchy();
+    if let Some(what) = hierarchy.limitations() {
+        println!("  cgroup hierarchy {}: {}", hierarchy, what);
+    }
+    let l = Ladder::new(ladder::rungs_for(cfg));
+    for (i, rung) in l.rungs().iter().enumerate() {
+        println!("  {}. {:<16} when {:<5} timeout {}s", i + 1, rung.action.as_str(),
+                 format!("{:?}", rung.when).to_lowercase(), rung.timeout_sec);
+    }
+
+    println!("\nIf {:?} pressure persists:", state);
+    let plan = l.plan(state, std::time::Duration::from_secs(cfg.scan_interval_sec), std::time::Duration::from_secs(600));
+    if plan.is_empty() {
+        println!("  no rung applies");
+    }
+    let target = victim.map(|v| format!("PID {} ({})", v.pid, v.name)).unwrap_or_else(|| "no candidate".into());
+    let mut seen = Vec::new();
+    for (t, i) in plan {
+        if seen.contains(&i) {
+            println!("  {:<7} repeat rung {} every {}s while pressure lasts", format!("t+{}s", t.as_secs()), i + 1,
+                     l.rungs()[i].timeout_sec.max(cfg.scan_interval_sec));
+            continue;
+        }
+        seen.push(i);
+        let action = l.rungs()[i].action;
+        let what = match action {
+            LadderAction::ReleaseReserve => format!("release reserve ({} MB)", cfg.reserve_mb),
+            LadderAction::Reclaim => format!("memory.reclaim up to {}% of usage per request from user/machine slices{} until avail% reaches {}%",
+                                             cfg.reclaim_step_pct,
+                                             if cfg.reclaim_units.is_empty() { String::new() } else { format!(" and {}", cfg.reclaim_units.join(", ")) },
+                                             cfg.soft_threshold_pct.saturating_add(cfg.hysteresis_pct)),
+            LadderAction::Throttle => format!("lower memory.high to {}% of usage on the worst cgroup", cfg.soft_throttle_pct),
+            LadderAction::Freeze => format!("freeze {} ({})", target, cfg.freeze_granularity),
+            LadderAction::Term => format!("SIGTERM {}", target),
+            LadderAction::Kill => format!("kill {} ({})", target, cfg.kill_granularity),
+        };
+        let note = match action {
+            _ if cfg.mode == "watch" && action.acts_on_processes() => " [watch: skipped]",
+            LadderAction::Reclaim | LadderAction::Throttle if hierarchy != Hierarchy::Unified => " [no v2 memory controller: skipped]",
+            LadderAction::Freeze if cfg.freeze_granularity == "cgroup"
+                && matches!(hierarchy, Hierarchy::Legacy | Hierarchy::Unavailable) => " [no cgroup.freeze: SIGSTOP per process]",
+            _ => "",
+        };
+        println!("  {:<7} {}{}", format!("t+{}s", t.as_secs()), what, note);
+    }
+}
+
+fn print_breakdown(m: &MemInfo) {
+    let b = m.breakdown();
+    let pct = |kb: u64| kb as f64 / m.mem_total as f64 * 100.0;
+    let mut table = Table::new();
+    table.set_content_arrangement(ContentArrangement::Dynamic);
+    table.set_header(vec!["Memory", "Size", "%", "What it is"]);
+    let rows = [
+        ("Anonymous", b.anon, "process heap, stacks, private mappings"),
+        ("File cache", b.file_cache, "page cache and buffers, mostly reclaimable"),
+        ("Shmem/tmpfs", b.shmem, "tmpfs, /dev/shm, shared anonymous memory"),
+        ("Slab (reclaimable)", b.slab_reclaimable, "dentry/inode caches"),
+        ("Slab (unreclaimable)", b.slab_unreclaimable, "kernel objects"),
+        ("Kernel", b.kernel, "kernel stacks, page tables, per-cpu"),
+        ("HugeTLB", b.hugetlb, "reserved huge pages"),
+        ("Free", b.free, "unused"),
+        ("Other", b.other, "vmalloc, drivers, not itemised"),
+    ];
+    for (name, kb, what) in rows {
+        if kb == 0 && name == "HugeTLB" {
+            continue;
+        }
+        table.add_row(vec![
+            name.to_string(),
+            format_size(kb * 1024, BINARY),
+            format!("{:.1}", pct(kb)),
+            what.to_string(),
+        ]);
+    }
+    println!("{}", table);
+    println!("Committed: {} of {} commit limit; dirty {}, writeback {}, swap cached {}",
+             format_size(m.committed_as * 1024, BINARY),
+             format_size(m.commit_limit * 1024, BINARY),
+             format_size(m.dirty * 1024, BINARY),
+             format_size(m.writeback * 1024, BINARY),
+             format_size(m.swap_cached * 1024, BINARY));
+}
+
+fn print_compressed_swap(comp: &CompressedSwap) {
+    let ratio = |r: Option<f64>| r.map(|r| format!("{:.1}x", r)).unwrap_or_else(|| "-".into());
+    for d in &comp.zram {
+        println!("  {}: {} stored in {} of RAM ({}), disksize {}",
+                 d.name,
+                 format_size(d.orig_data_size, BINARY),
+                 format_size(d.mem_used_total, BINARY),
+                 ratio(d.ratio()),
+                 format_size(d.disksize, BINARY));
+    }
+    if let Some(z) = comp.zswap.as_ref().filter(|z| z.enabled) {
+        print!("  zswap: enabled");
+        if let Some(ref c) = z.compressor {
+            print!(" ({}", c);
+            if let Some(pct) = z.max_pool_percent {
+                print!(", pool max {}% of RAM", pct);
+            }
+            print!(")");
+        }
+        if let Some(pool) = z.pool_bytes {
+            print!(", pool {} holding {} ({})",
+                   format_size(pool, BINARY),
+                   format_size(z.stored_bytes.unwrap_or(0), BINARY),
+                   ratio(z.ratio()));
+        }
+        println!();
+    }
+    if !comp.is_empty() {
+        println!("  compressed swap RAM in use: {}", format_size(comp.ram_used_bytes(), BINARY));
+    }
+}
+
+fn reserve_state(held: bool, chunks_held: usize, chunks_total: usize) -> String {
+    if !held {
+        "released".into()
+    } else if chunks_held < chunks_total {
+        format!("partly held, {}/{} chunks", chunks_held, chunks_total)
+    } else {
+        "held".into()
+    }
+}
+
+/* Requests that only make sense against a running daemon */
+fn daemon_request(req: Request) -> Result<Response> {
+    match control::request(req)? {
+        Response::Error { message } => Err(anyhow::anyhow!("daemon refused request: {}", message)),
+        other => Ok(other),
+    }
+}
+
+fn unexpected(resp: Response) -> anyhow::Error {
+    anyhow::anyhow!("unexpected daemon response: {:?}", resp)
+}
+
+fn daemon_status() -> Option<DaemonStatus> {
+    match control::request(Request::Status) {
+        Ok(Response::Status(st)) => Some(*st),
+        _ => None,
+    }
+}
+
+fn format_decision(d: &Decision) -> String {
+    let target = match (&d.target_pid, &d.target_name) {
+        (Some(pid), Some(name)) => format!(" → PID {} ({})", pid, name),
+        (Some(pid), None) => format!(" → PID {}", pid),
+        _ => String::new(),
+    };
+    let ago = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|n| n.as_secs().saturating_sub(d.at_unix))
+        .unwrap_or(0);
+    format!(
+        "{}{} [{:?}] {} ({} ago)",
+        d.action,
+        target,
+        d.state,
+        d.reason,
+        humantime::format_duration(std::time::Duration::from_secs(ago))
+    )
+}
+
 fn slices_cmd(tree: bool) -> Result<()> {
     let _tree = tree;
     println!("Slices command not yet implemented");
     Ok(())
 }
 
-fn status(_unicode: bool, json: bool, watch: bool) -> Result<()> {
+fn status(_unicode: bool, json: bool, watch: bool, breakdown: bool) -> Result<()> {
     loop {
         if json {
             status_json()?;
         } else {
-            status_table()?;
+            status_table(breakdown)?;
         }
         
         if !watch {
@@ -177,39 +398,58 @@ struct StatusOutput {
     psi_available: bool,
     psi_some_avg10: Option<f64>,
     psi_full_avg10: Option<f64>,
+    swap_total_bytes: u64,
+    swap_free_bytes: u64,
+    swap_effective_free_bytes: u64,
+    compressed_swap: CompressedSwap,
+    meminfo: MemInfo,
+    breakdown: MemBreakdown,
+    cgroup_hierarchy: Hierarchy,
+    daemon: Option<DaemonStatus>,
 }
 
 fn status_json() -> Result<()> {
     let m = mem::sample()?;
-    let state = policy::classify(m.avail_pct, 15, 5);
-    let used = m.mem_total.saturating_sub(m.mem_available);
+    let daemon = daemon_status();
+    let state = daemon.as_ref().map(|d| d.state).unwrap_or_else(|| policy::classify(m.avail_pct(), 15, 5));
+    let used = m.used();
     
     let psi_metrics = PSIMetrics::sample().ok();
+    let comp = CompressedSwap::sample();
     
     let output = StatusOutput {
         state: format!("{:?}", state),
-        avail_pct: m.avail_pct,
+        avail_pct: m.avail_pct(),
         mem_total_bytes: m.mem_total * 1024,
-        mem_available_bytes: m.mem_available * 1024,
+        mem_available_bytes: m.available() * 1024,
         mem_used_bytes: used * 1024,
         psi_available: psi_metrics.is_some(),
         psi_some_avg10: psi_metrics.as_ref().map(|p| p.some_avg10),
         psi_full_avg10: psi_metrics.as_ref().map(|p| p.full_avg10),
+        swap_total_bytes: m.swap_total * 1024,
+        swap_free_bytes: m.swap_free * 1024,
+        swap_effective_free_bytes: comp.effective_swap_free_kb(&m) * 1024,
+        compressed_swap: comp,
+        breakdown: m.breakdown(),
+        meminfo: m,
+        cgroup_hierarchy: cgroups::hierarchy(),
+        daemon,
     };
     
     println!("{}", serde_json::to_string_pretty(&output)?);
     Ok(())
 }
 
-fn status_table() -> Result<()> {
+fn status_table(breakdown: bool) -> Result<()> {
     println!("Sentinel — Status");
     match mem::sample() {
         Ok(m) => {
-            let state = policy::classify(m.avail_pct, 15, 5);
+            let daemon = daemon_status();
+            let state = daemon.as_ref().map(|d| d.state).unwrap_or_else(|| policy::classify(m.avail_pct(), 15, 5));
             let mut table = Table::new();
             table.set_content_arrangement(ContentArrangement::Dynamic);
             table.set_header(vec!["State", "MemAvailable", "Total", "Used"]);
-            let used = m.mem_total.saturating_sub(m.mem_available);
+            let used = m.used();
             let colorized = match state {
                 PressureState::Healthy => "● Healthy".to_string(),
                 PressureState::Soft => "▲ Soft".to_string(),
@@ -217,11 +457,32 @@ fn status_table() -> Result<()> {
             };
             table.add_row(vec![
                 colorized,
-                format!("{:.0}%", m.avail_pct),
-                format_size(m.mem_total as u64 * 1024, BINARY),
-                format_size(used as u64 * 1024, BINARY),
+                format!("{:.0}%", m.avail_pct()),
+                format_size(m.mem_total * 1024, BINARY),
+                format_size(used * 1024, BINARY),
             ]);
             println!("{}", table);
+
+            if breakdown {
+                print_breakdown(&m);
+            }
+
+            let comp = CompressedSwap::sample();
+            match m.swap_free_pct() {
+                Some(free_pct) => {
+                    print!("Swap: {} free of {} ({:.0}%)",
+                           format_size(m.swap_free * 1024, BINARY),
+                           format_size(m.swap_total * 1024, BINARY),
+                           free_pct);
+                    let effective = comp.effective_swap_free_kb(&m);
+                    if effective < m.swap_free {
+                        print!(", {} usable given RAM left for zram", format_size(effective * 1024, BINARY));
+                    }
+                    println!();
+                }
+                None => println!("Swap: none"),
+            }
+            print_compressed_swap(&comp);
             
             if let Ok(psi) = PSIMetrics::sample() {
                 println!("\nPSI Memory Pressure:");
@@ -230,6 +491,57 @@ fn status_table() -> Result<()> {
                 println!("  full avg10: {:.2}%  avg60: {:.2}%  avg300: {:.2}%",
                          psi.full_avg10, psi.full_avg60, psi.full_avg300);
             }
+
+            let hierarchy = cgroups::hierarchy();
+            match hierarchy.limitations() {
+                Some(what) => println!("\nCgroups: {} ({})", hierarchy, what),
+                None => println!("\nCgroups: {} (cgroup v2)", hierarchy),
+            }
+
+            match daemon {
+                Some(d) => {
+                    println!("\nDaemon (PID {}, v{}, up {}):", d.pid, d.version,
+                             humantime::format_duration(std::time::Duration::from_secs(d.uptime_secs)));
+                    println!("  mode: {}", d.mode);
+                    println!("  state: {:?} for {}", d.state,
+                             humantime::format_duration(std::time::Duration::from_secs(d.state_since_secs)));
+                    if let Some(ref t) = d.last_transition {
+                        println!("  last transition: {:?} -> {:?} after {}s ({})", t.from, t.to, t.held_secs, t.cause);
+                    }
+                    println!("  reserve: {} ({} MB configured, {} resident{})",
+                             reserve_state(d.reserve_held, d.reserve_chunks_held, d.reserve_chunks_total),
+                             d.reserve_mb,
+                             format_size(d.reserve_resident_bytes, BINARY),
+                             if d.reserve_locked { ", mlocked" } else { "" });
+                    println!("  action budget: {} left this minute (+{} emergency)",
+                             d.actions_remaining, d.emergency_actions_remaining);
+                    println!("  trend: {}", format_trend(d.headroom_rate_kb_s, d.exhaustion_secs));
+                    match (&d.ladder_rung, d.ladder_position) {
+                        (Some(rung), Some(pos)) => println!("  ladder: {} (rung {}/{}, {}s)",
+                                                            rung, pos, d.ladder_len, d.ladder_rung_secs.unwrap_or(0)),
+                        _ => println!("  ladder: idle ({} rungs)", d.ladder_len),
+                    }
+                    for cg in &d.throttled_cgroups {
+                        println!("  throttled cgroup (memory.high): {}", cg);
+                    }
+                    if d.frozen_pids.is_empty() && d.frozen_cgroups.is_empty() {
+                        println!("  frozen: none");
+                    } else {
+                        if !d.frozen_pids.is_empty() {
+                            let pids: Vec<String> = d.frozen_pids.iter().map(|p| p.to_string()).collect();
+                            println!("  frozen processes: {} (PIDs {})", d.frozen_pids.len(), pids.join(", "));
+                        }
+                        for cg in &d.frozen_cgroups {
+                            println!("  frozen cgroup: {}", cg);
+                        }
+                    }
+                    match d.last_decision {
+                        Some(ref dec) => println!("  last decision: {}", format_decision(dec)),
+                        None => println!("  last decision: none"),
+                    }
+                }
+                None => println!("\nDaemon: not running (state computed locally with default thresholds)"),
+            }
         }
         Err(e) => {
             println!("ERROR: Could not sample memory: {}", e);
@@ -241,7 +553,10 @@ fn status_table() -> Result<()> {
 }
 
 fn top(limit: usize, unicode: bool, json: bool) -> Result<()> {
-    let procs = procinfo::top_processes(limit, &vec!["sshd".into(), "systemd".into(), "sentinel".into()])?;
+    let cfg = current_config();
+    let exclude = Exclusions::new(&cfg.exclude_names).unwrap_or_default();
+    let classes = SliceClasses::new(&cfg.slice_classes).unwrap_or_default();
+    let procs = procinfo::top_processes(limit, &exclude, &classes)?;
     
     if json {
         #[derive(Serialize)]
@@ -249,21 +564,30 @@ fn top(limit: usize, unicode: bool, json: bool) -> Result<()> {
             pid: i32,
             name: String,
             rss_bytes: u64,
+            pss_bytes: Option<u64>,
+            uss_bytes: Option<u64>,
+            class: Option<String>,
         }
         
         let output: Vec<TopOutput> = procs.iter().map(|p| TopOutput {
             pid: p.pid,
             name: p.name.clone(),
             rss_bytes: p.rss_bytes,
+            pss_bytes: p.pss_bytes,
+            uss_bytes: p.uss_bytes,
+            class: p.class.clone(),
         }).collect();
         
         println!("{}", serde_json::to_string_pretty(&output)?);
     } else {
         let mut table = Table::new();
         if unicode { table.load_preset(UTF8_FULL); }
-        table.set_header(vec!["PID","NAME","RSS"]);
+        table.set_header(vec!["PID","NAME","RSS","PSS","USS","CLASS"]);
+        // PSS/USS need ptrace access to the process (root for other users')
+        let size = |b: Option<u64>| b.map(|b| humansize::format_size(b, BINARY)).unwrap_or_else(|| "-".into());
         for p in procs {
-            table.add_row(vec![p.pid.to_string(), p.name, humansize::format_size(p.rss_bytes, BINARY)]);
+            table.add_row(vec![p.pid.to_string(), p.name, humansize::format_size(p.rss_bytes, BINARY),
+                               size(p.pss_bytes), size(p.uss_bytes), p.class.unwrap_or_else(|| "-".into())]);
         }
         println!("{}", table);
     }
@@ -271,51 +595,104 @@ fn top(limit: usize, unicode: bool, json: bool) -> Result<()> {
     Ok(())
 }
 
-fn simulate(level: &str, dry_run: bool, explain: bool) -> Result<()> {
+/* The config the daemon has loaded, else the config file, else defaults */
+fn current_config() -> sentinel_core::config::Config {
     use sentinel_core::config::Config;
-    
+    match control::request(Request::Config) {
+        Ok(Response::Config(cfg)) => *cfg,
+        _ => Config::load_from(std::path::Path::new("/etc/memsentinel.toml")).unwrap_or_default(),
+    }
+}
+
+fn simulate(level: &str, dry_run: bool, explain: bool) -> Result<()> {
     println!("Simulating {} threshold response{}", level, if dry_run { " (dry-run)" } else { "" });
     
     if explain {
-        let cfg = Config::default();
+        // Walk the ladder the running daemon would walk
+        let cfg = current_config();
+        let state = if level == "hard" { PressureState::Hard } else { PressureState::Soft };
         let m = mem::sample()?;
         
         println!("\n=== Current Memory State ===");
         println!("Total: {} KB", m.mem_total);
-        println!("Available: {} KB ({:.1}%)", m.mem_available, m.avail_pct);
+        println!("Available: {} KB ({:.1}%)", m.available(), m.avail_pct());
         
         if let Ok(psi) = PSIMetrics::sample() {
             println!("\n=== PSI Metrics ===");
             println!("some avg10: {:.2}%", psi.some_avg10);
             println!("full avg10: {:.2}%", psi.full_avg10);
         }
+
+        println!("\n=== Trend ===");
+        let trend = sample_trend()?;
+        let tte = trend.time_to_exhaustion();
+        println!("{} (sampled locally over {}s)",
+                 format_trend(trend.rate_kb_per_sec(), tte.map(|t| t.as_secs())), TREND_SAMPLE_SECS);
+        let predicted = policy::classify_trend(
+            tte,
+            std::time::Duration::from_secs(cfg.trend_soft_horizon_sec),
+            std::time::Duration::from_secs(cfg.trend_hard_horizon_sec),
+            state,
+        );
+        if predicted != PressureState::Healthy {
+            println!("→ Pre-emptive {:?}: exhaustion within the {}s horizon", predicted,
+                     if predicted == PressureState::Hard { cfg.trend_hard_horizon_sec } else { cfg.trend_soft_horizon_sec });
+        }
         
         println!("\n=== Process Badness Scoring ===");
-        match procinfo::processes_with_badness(
-            &cfg.exclude_names,
-            &cfg.protected_units,
-            m.mem_total * 1024,
-        ) {
-            Ok(procs) => {
-                println!("{:<8} {:<20} {:<12} {:<10} {:<15} {:<10}",
-                         "PID", "NAME", "RSS (MB)", "OOM ADJ", "SLICE", "BADNESS");
-                println!("{}", "-".repeat(85));
+        let rules = RuleSet::new(&cfg.rules)?;
+        let classes = SliceClasses::new(&cfg.slice_classes)?;
+        let scoring = procinfo::Scoring::new(&cfg, &rules, &classes, m.mem_total * 1024);
+        let exclude = Exclusions::new(&cfg.exclude_names).unwrap_or_default();
+        println!("(badness_mode = \"{}\")", cfg.badness_mode);
+        let protected = ProtectedUnits::new(&cfg.protected_units)?;
+        match procinfo::processes_with_badness(&exclude, &protected, &scoring) {
+            Ok(mut procs) => {
+                if cfg.subtree_badness {
+                    let tree = sentinel_core::proctree::ProcTree::build()?;
+                    procs = procinfo::group_by_subtree(procs, &tree, &exclude, &protected, &scoring);
+                    println!("(ranked by process subtree; RSS and FREED cover the whole subtree)");
+                }
+                println!("{:<8} {:<20} {:<10} {:<10} {:<11} {:<10} {:<10} {:<15} {:<10} {:<10} {:<5}",
+                         "PID", "NAME", "RSS (MB)", "PSS (MB)", "FREED (MB)", "OOM ADJ", "OOM SCORE", "CLASS", "CG STALL", "BADNESS", "RULES");
+                println!("{}", "-".repeat(136));
                 
                 for proc in procs.iter().take(10) {
-                    println!("{:<8} {:<20} {:<12} {:<10} {:<15} {:<10.1}",
+                    println!("{:<8} {:<20} {:<10} {:<10} {:<11} {:<10} {:<10} {:<15} {:<10} {:<10.1} {}",
                              proc.pid,
                              proc.name,
-                             proc.rss_bytes / (1024*1024),
+                             proc.subtree.as_ref().map(|t| t.rss_bytes).unwrap_or(proc.rss_bytes) / (1024*1024),
+                             proc.mem.pss.map(|b| (b / (1024*1024)).to_string()).unwrap_or_else(|| "-".into()),
+                             proc.freed_bytes() / (1024*1024),
                              proc.oom_score_adj,
-                             format!("{:?}", proc.cgroup_slice),
-                             proc.badness_score);
+                             proc.oom_score.map(|s| s.to_string()).unwrap_or_else(|| "-".into()),
+                             proc.class_name().to_string(),
+                             proc.cgroup_psi_some.map(|s| format!("{:.2}%", s)).unwrap_or_else(|| "-".into()),
+                             proc.badness_score,
+                             format_rules(proc));
                 }
                 
                 if let Some(victim) = procs.first() {
                     println!("\n→ Target selected: PID {} ({})", victim.pid, victim.name);
                     println!("  Badness score: {:.1}", victim.badness_score);
-                    println!("  Cgroup: {:?} / {:?}", victim.cgroup_slice, victim.cgroup_unit);
+                    if !victim.rules_fired.is_empty() {
+                        println!("  Rules: {}{}", victim.rules_fired.join(", "),
+                                 if victim.is_sacrificial() { " (sacrificial: ranked ahead of unmarked processes)" } else { "" });
+                    }
+                    println!("  Memory freed by a kill: ~{} MB ({})",
+                             victim.freed_bytes() / (1024*1024),
+                             if victim.mem.uss.is_some() { "USS + swap share" } else { "anon + swap; smaps_rollup unreadable" });
+                    if let Some(ref t) = victim.subtree {
+                        println!("  Subtree: {} processes; kill_granularity = \"{}\"{}", t.procs, cfg.kill_granularity,
+                                 if t.procs > 1 && cfg.kill_granularity == "process" { " signals the head only" } else { "" });
+                    }
+                    println!("  Cgroup: {} (priority {}) / {:?}", victim.class_name(), victim.cgroup_priority(), victim.cgroup_unit);
+                    if let Some(stall) = victim.cgroup_psi_some {
+                        println!("  Cgroup memory.pressure some avg10: {:.2}%", stall);
+                    }
                 }
+                print_kernel_choice(procs.first());
+                print_ladder_plan(&cfg, state, procs.first());
             }
             Err(e) => {
                 println!("Error enumerating processes: {}", e);
@@ -326,6 +703,39 @@ fn simulate(level: &str, dry_run: bool, explain: bool) -> Result<()> {
     Ok(())
 }
 
+/* `[[rules]]` that matched, with `*` for a sacrificial mark */
+fn format_rules(proc: &procinfo::ProcWithBadness) -> String {
+    if proc.rules_fired.is_empty() {
+        return "-".into();
+    }
+    format!("{}{}", proc.rules_fired.join(","), if proc.is_sacrificial() { "*" } else { "" })
+}
+
+/* What the kernel OOM killer would pick, for comparison with our victim */
+fn print_kernel_choice(victim: Option<&procinfo::ProcWithBadness>) {
+    match procinfo::kernel_oom_choice() {
+        Ok(Some(k)) => {
+            println!("\n→ Kernel OOM killer would pick: PID {} ({}, {} MB RSS)", k.pid, k.name, k.rss_bytes / (1024*1024));
+            match victim {
+                Some(v) if v.pid == k.pid => println!("  Same choice as sentinel"),
+                Some(v) if v.subtree.is_some() && in_subtree(v.pid, k.pid) => {
+                    println!("  Inside sentinel's chosen subtree (PID {})", v.pid)
+                }
+                Some(_) => println!("  Differs from sentinel; excluded and protected processes are fair game to the kernel"),
+                None => {}
+            }
+        }
+        Ok(None) => println!("\n→ Kernel OOM killer: no eligible process"),
+        Err(e) => println!("\n→ Kernel OOM choice unavailable: {}", e),
+    }
+}
+
+fn in_subtree(root: i32, pid: i32) -> bool {
+    sentinel_core::proctree::ProcTree::build()
+        .map(|t| t.subtree_leaves_first(root).contains(&pid))
+        .unwrap_or(false)
+}
+
 fn init_config_interactive() -> Result<()> {
     use sentinel_core::config::Config;
     use std::fs;
@@ -368,6 +778,7 @@ fn init_config_interactive() -> Result<()> {
     
     println!("\n🛡️  Protected Processes");
     println!("Current: {:?}", cfg.exclude_names);
+    println!("Names match comm exactly; use exe:NAME, path:/PATH, glob:PAT or regex:RE for anything else");
     print!("Add more protected process names (comma-separated, or press Enter to skip): ");
     io::stdout().flush()?;
     let mut exclude_input = String::new();
@@ -379,6 +790,10 @@ fn init_config_interactive() -> Result<()> {
                 cfg.exclude_names.push(trimmed);
             }
         }
+        cfg.validate()?;
+        for w in cfg.warnings() {
+            println!("⚠️  {}", w);
+        }
     }
 
     let toml_content = toml::to_string_pretty(&cfg)?;
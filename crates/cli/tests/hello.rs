@@ -1,4 +1,4 @@
+#[test]
 fn hello_world_prints() {
     println!("Hello, world!");
-    assert!(true);
 }
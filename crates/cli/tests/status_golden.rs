@@ -1,8 +1,8 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 
 #[test]
 fn status_output_ascii() {
-    let mut cmd = Command::cargo_bin("sentinelctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("sentinelctl");
     cmd.arg("--unicode").arg("false").arg("status");
     let output = cmd.output().unwrap();
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -1,6 +1,12 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
+use nix::errno::Errno;
 use nix::sys::signal::{kill, Signal};
 use nix::unistd::Pid;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::proctree::{ProcNode, ProcTree};
 
 pub fn sigstop(pid: i32) -> Result<()> {
     kill(Pid::from_raw(pid), Signal::SIGSTOP).map_err(|e| anyhow!(e))?;
@@ -22,8 +28,19 @@ pub fn sigkill(pid: i32) -> Result<()> {
     Ok(())
 }
 
+/* Fails unless `pid` is still the process that started at `start_time`
+ * (clock ticks after boot), so a PID recycled since the scan is left alone */
+pub fn check_identity(pid: i32, start_time: u64) -> Result<()> {
+    match procfs::process::Process::new(pid).and_then(|p| p.stat()) {
+        Ok(stat) if stat.starttime == start_time => Ok(()),
+        Ok(_) => Err(anyhow!("pid {} was reused by another process", pid)),
+        Err(_) => Err(anyhow!("pid {} has already exited", pid)),
+    }
+}
+
 /* Try SIGTERM first, then SIGKILL if process still exists after 100ms */
-pub fn kill_process(pid: i32) -> Result<()> {
+pub fn kill_process(pid: i32, start_time: u64) -> Result<()> {
+    check_identity(pid, start_time)?;
     sigterm(pid)?;
     std::thread::sleep(std::time::Duration::from_millis(100));
     
@@ -34,3 +51,145 @@ pub fn kill_process(pid: i32) -> Result<()> {
     Ok(())
 }
 
+
+/* cgroup v2 freezer (kernel 5.2+): stops every task in the cgroup at once */
+pub fn cgroup_freeze_supported(cgroup: &Path) -> bool {
+    cgroup.join("cgroup.freeze").exists()
+}
+
+pub fn cgroup_freeze(cgroup: &Path) -> Result<()> {
+    write_cgroup_freeze(cgroup, "1")
+}
+
+pub fn cgroup_thaw(cgroup: &Path) -> Result<()> {
+    write_cgroup_freeze(cgroup, "0")
+}
+
+fn write_cgroup_freeze(cgroup: &Path, value: &str) -> Result<()> {
+    let file = cgroup.join("cgroup.freeze");
+    fs::write(&file, value).with_context(|| format!("writing {} to {}", value, file.display()))
+}
+
+/* SIGTERM the subtree rooted at `pid`, leaves first so no parent is left
+ * to respawn children, then SIGKILL whatever is left in the same order.
+ * Descendants failing `joins` are spared along with their own children.
+ * Fails if `pid` is gone or no longer started at `start_time`. */
+pub fn kill_process_tree(pid: i32, start_time: u64, joins: impl Fn(&ProcNode) -> bool) -> Result<()> {
+    let tree = ProcTree::build()?;
+    match tree.get(pid) {
+        Some(root) if root.start_time == start_time => {}
+        Some(_) => return Err(anyhow!("pid {} was reused by another process", pid)),
+        None => return Err(anyhow!("pid {} has already exited", pid)),
+    }
+    let order = tree.subtree_where(pid, joins);
+    for &p in &order {
+        let _ = sigterm(p);
+    }
+    std::thread::sleep(Duration::from_millis(100));
+
+    for &p in &order {
+        if Path::new(&format!("/proc/{}", p)).exists() {
+            let _ = sigkill(p);
+        }
+    }
+    Ok(())
+}
+
+pub fn cgroup_kill_supported(cgroup: &Path) -> bool {
+    cgroup.join("cgroup.kill").exists()
+}
+
+/* SIGKILL every task in the cgroup: cgroup.kill on kernel 5.14+, otherwise
+ * signal each PID in cgroup.procs until the group is empty */
+pub fn kill_cgroup(cgroup: &Path) -> Result<()> {
+    if cgroup_kill_supported(cgroup) {
+        let file = cgroup.join("cgroup.kill");
+        return fs::write(&file, "1").with_context(|| format!("writing {}", file.display()));
+    }
+
+    let procs = cgroup.join("cgroup.procs");
+    // Tasks may fork while we are signalling; re-read a few times
+    for _ in 0..10 {
+        let content = fs::read_to_string(&procs).with_context(|| format!("reading {}", procs.display()))?;
+        let pids: Vec<i32> = content.lines().filter_map(|l| l.trim().parse().ok()).collect();
+        if pids.is_empty() {
+            return Ok(());
+        }
+        for pid in pids {
+            let _ = sigkill(pid);
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Err(anyhow!("{} still has tasks after repeated SIGKILL", cgroup.display()))
+}
+
+/* cgroup v2 memory.high: above it the kernel reclaims and throttles the
+ * group's allocations instead of letting it grow */
+pub fn write_memory_high(cgroup: &Path, value: &str) -> Result<()> {
+    let file = cgroup.join("memory.high");
+    fs::write(&file, value).with_context(|| format!("writing {} to {}", value, file.display()))
+}
+
+/* cgroup v2 memory.reclaim (kernel 5.19+). Returns false when the kernel
+ * gave up before reclaiming all of `bytes` (EAGAIN) - part of it may still
+ * have been freed. */
+pub fn write_memory_reclaim(cgroup: &Path, bytes: u64) -> Result<bool> {
+    let file = cgroup.join("memory.reclaim");
+    match fs::write(&file, bytes.to_string()) {
+        Ok(()) => Ok(true),
+        Err(e) if e.raw_os_error() == Some(Errno::EAGAIN as i32) => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("writing {} to {}", bytes, file.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_kill_process_tree() {
+        let mut sh = Command::new("sh")
+            .args(["-c", "sleep 30 & sleep 30 & wait"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let root = sh.id() as i32;
+
+        let mut tree = Vec::new();
+        for _ in 0..100 {
+            tree = ProcTree::build().unwrap().subtree_leaves_first(root);
+            if tree.len() >= 3 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*tree.last().unwrap(), root);
+        assert_eq!(tree.len(), 3);
+
+        let start = ProcTree::build().unwrap().get(root).unwrap().start_time;
+        assert!(kill_process_tree(root, start + 1, |_| true).is_err());
+        // The first sleep is spared
+        kill_process_tree(root, start, |n| n.pid != tree[0]).unwrap();
+        let _ = sh.wait();
+        let state = |pid: i32| procfs::process::Process::new(pid).and_then(|p| p.stat()).map(|s| s.state);
+        assert!(state(tree[1]).map(|s| s == 'Z').unwrap_or(true));
+        assert!(state(tree[0]).map(|s| s != 'Z').unwrap_or(false));
+        let _ = sigkill(tree[0]);
+        assert!(kill_process_tree(root, start, |_| true).is_err());
+    }
+
+    #[test]
+    fn test_kill_process_checks_identity() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id() as i32;
+        let start = procfs::process::Process::new(pid).unwrap().stat().unwrap().starttime;
+
+        assert!(kill_process(pid, start + 1).is_err());
+        assert!(check_identity(pid, start).is_ok());
+        kill_process(pid, start).unwrap();
+        let _ = child.wait();
+        assert!(check_identity(pid, start).is_err());
+    }
+}
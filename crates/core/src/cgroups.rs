@@ -1,7 +1,93 @@
 use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/* How cgroups are mounted. Freeze, cgroup.kill, memory.high, memory.reclaim
+ * and per-cgroup PSI are cgroup v2 interfaces; elsewhere the actions that
+ * need them fall back to per-process signals or are skipped. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Hierarchy {
+    /* cgroup v2 only, mounted at /sys/fs/cgroup */
+    Unified,
+    /* v1 controllers plus a controller-less v2 tree at /sys/fs/cgroup/unified */
+    Hybrid,
+    /* v1 only; systemd's own tree at /sys/fs/cgroup/systemd */
+    Legacy,
+    /* No cgroup filesystem found */
+    Unavailable,
+}
+
+impl Hierarchy {
+    pub fn detect() -> Self {
+        Self::detect_at(Path::new(CGROUP_ROOT))
+    }
+
+    pub fn detect_at(root: &Path) -> Self {
+        if root.join("cgroup.controllers").exists() {
+            Hierarchy::Unified
+        } else if root.join("unified/cgroup.controllers").exists() {
+            Hierarchy::Hybrid
+        } else if root.join("memory").is_dir() || root.join("systemd").is_dir() {
+            Hierarchy::Legacy
+        } else {
+            Hierarchy::Unavailable
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Hierarchy::Unified => "unified",
+            Hierarchy::Hybrid => "hybrid",
+            Hierarchy::Legacy => "legacy",
+            Hierarchy::Unavailable => "unavailable",
+        }
+    }
+
+    /* Where cgroup paths from /proc/<pid>/cgroup live on disk */
+    pub fn mount(&self) -> PathBuf {
+        match self {
+            Hierarchy::Hybrid => Path::new(CGROUP_ROOT).join("unified"),
+            Hierarchy::Legacy => Path::new(CGROUP_ROOT).join("systemd"),
+            Hierarchy::Unified | Hierarchy::Unavailable => PathBuf::from(CGROUP_ROOT),
+        }
+    }
+
+    /* What doesn't work in this mode, for logs and status output */
+    pub fn limitations(&self) -> Option<&'static str> {
+        match self {
+            Hierarchy::Unified => None,
+            Hierarchy::Hybrid => Some(
+                "memory controller is on cgroup v1: no memory.high throttling, memory.reclaim or per-cgroup PSI; \
+                 cgroup.freeze and cgroup.kill work on kernels that have them",
+            ),
+            Hierarchy::Legacy => Some(
+                "cgroup v1 only: freezing falls back to SIGSTOP per process, whole-cgroup kills signal each PID \
+                 in systemd's tree, no memory.high throttling, memory.reclaim or per-cgroup PSI",
+            ),
+            Hierarchy::Unavailable => Some("no cgroup filesystem: every action is per process and slices are unknown"),
+        }
+    }
+}
+
+impl fmt::Display for Hierarchy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/* Detected once; remounting cgroups under a running daemon isn't supported */
+pub fn hierarchy() -> Hierarchy {
+    static HIERARCHY: OnceLock<Hierarchy> = OnceLock::new();
+    *HIERARCHY.get_or_init(Hierarchy::detect)
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum CgroupSlice {
@@ -23,11 +109,104 @@ impl CgroupSlice {
             CgroupSlice::Init => 0,         // critical init processes
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CgroupSlice::User => "user",
+            CgroupSlice::Machine => "machine",
+            CgroupSlice::Unknown => "unknown",
+            CgroupSlice::System => "system",
+            CgroupSlice::Init => "init",
+        }
+    }
+}
+
+impl fmt::Display for CgroupSlice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/* `[[slice_classes]]`: cgroups under `match` are ranked as `class` with
+ * `priority` in place of the built-in slice's priority. The first matching
+ * entry wins. The slice itself is kept for everything besides ranking. */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SliceClass {
+    /* Cgroup path, matched as a glob against the path and each of its
+     * ancestors, so "/batch.slice" covers everything below it */
+    #[serde(rename = "match")]
+    pub pattern: String,
+    pub class: String,
+    pub priority: i32,
+}
+
+/* '*' must not cross '/' when matching whole paths */
+const PATH_MATCH: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/* `slice_classes`, compiled once per config load */
+#[derive(Debug, Default)]
+pub struct SliceClasses {
+    entries: Vec<(Pattern, Class)>,
+}
+
+impl SliceClasses {
+    pub fn new(classes: &[SliceClass]) -> Result<Self> {
+        let entries = classes
+            .iter()
+            .map(|c| {
+                let pat = Pattern::new(&c.pattern).with_context(|| format!("slice_classes match {:?}: invalid glob", c.pattern))?;
+                if c.class.is_empty() {
+                    anyhow::bail!("slice_classes match {:?}: class must not be empty", c.pattern);
+                }
+                Ok((pat, Class { name: c.class.clone(), priority: c.priority }))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+}
+
+#[derive(Debug)]
+enum UnitMatch {
+    /* Entry without '/': a slice, service or scope name */
+    Component(Pattern),
+    /* Entry with '/': the path or one of its ancestors */
+    Path(Pattern),
+}
+
+/* `protected_units`, compiled once per config load */
+#[derive(Debug, Default)]
+pub struct ProtectedUnits {
+    entries: Vec<UnitMatch>,
+}
+
+impl ProtectedUnits {
+    pub fn new(units: &[String]) -> Result<Self> {
+        let entries = units
+            .iter()
+            .map(|unit| {
+                let pat = Pattern::new(unit).with_context(|| format!("protected_units entry {:?}: invalid glob", unit))?;
+                Ok(if unit.contains('/') { UnitMatch::Path(pat) } else { UnitMatch::Component(pat) })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+}
+
+/* The `slice_classes` entry a cgroup falls under */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Class {
+    pub name: String,
+    pub priority: i32,
 }
 
 #[derive(Debug, Clone)]
 pub struct CgroupInfo {
     pub slice: CgroupSlice,
+    pub class: Option<Class>,
     pub unit_name: Option<String>,
     pub raw_path: String,
 }
@@ -41,25 +220,48 @@ impl CgroupInfo {
         Self::parse(&content)
     }
 
+    /* A cgroup known by its path in the unified hierarchy, e.g. "/system.slice/foo.service" */
+    pub fn for_path(raw_path: &str) -> Self {
+        let (slice, unit_name) = Self::classify_path(raw_path);
+        CgroupInfo { slice, class: None, unit_name, raw_path: raw_path.to_string() }
+    }
+
+    /* Lines are "hierarchy-ID:controllers:path". The unified "0::" line
+     * is preferred; on v1 and hybrid systems it may just read "/", and then
+     * systemd's named hierarchy (which mirrors the unit layout), or failing
+     * that the memory controller, says where the process is. */
     fn parse(content: &str) -> Result<Self> {
+        let mut unified = None;
+        let mut systemd = None;
+        let mut memory = None;
         for line in content.lines() {
-            if line.contains("0::") {
-                let raw_path = line.strip_prefix("0::").unwrap_or(line).to_string();
-                let (slice, unit_name) = Self::classify_path(&raw_path);
-                
-                return Ok(CgroupInfo {
-                    slice,
-                    unit_name,
-                    raw_path,
-                });
+            let mut fields = line.splitn(3, ':');
+            let (Some(id), Some(controllers), Some(path)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            if id == "0" && controllers.is_empty() {
+                unified = Some(path);
+            } else if controllers == "name=systemd" {
+                systemd = Some(path);
+            } else if controllers.split(',').any(|c| c == "memory") {
+                memory = Some(path);
             }
         }
-        
-        Ok(CgroupInfo {
-            slice: CgroupSlice::Unknown,
-            unit_name: None,
-            raw_path: String::new(),
-        })
+
+        let found = [unified, systemd, memory].into_iter().flatten();
+        let raw_path = match found.clone().find(|p| *p != "/").or_else(|| found.clone().next()) {
+            Some(p) => p.to_string(),
+            None => {
+                return Ok(CgroupInfo {
+                    slice: CgroupSlice::Unknown,
+                    class: None,
+                    unit_name: None,
+                    raw_path: String::new(),
+                })
+            }
+        };
+        let (slice, unit_name) = Self::classify_path(&raw_path);
+        Ok(CgroupInfo { slice, class: None, unit_name, raw_path })
     }
 
     fn classify_path(path: &str) -> (CgroupSlice, Option<String>) {
@@ -75,26 +277,137 @@ impl CgroupInfo {
         } else if path.contains("/init.scope") {
             (CgroupSlice::Init, Some("init.scope".to_string()))
         } else {
-            (CgroupSlice::Unknown, None)
+            // Custom slices ("batch.slice") still name their units
+            (CgroupSlice::Unknown, Self::extract_unit_name(path))
         }
     }
 
     fn extract_unit_name(path: &str) -> Option<String> {
         path.split('/')
-            .filter(|s| s.ends_with(".service") || s.ends_with(".scope") || s.ends_with(".slice"))
-            .last()
+            .rfind(|s| s.ends_with(".service") || s.ends_with(".scope") || s.ends_with(".slice"))
             .map(|s| s.to_string())
     }
 
-    pub fn is_protected(&self, protected_units: &[String]) -> bool {
+    pub fn is_protected(&self, protected: &ProtectedUnits) -> bool {
+        self.protected_by(protected).is_some()
+    }
+
+    /* The part of the path that a `protected_units` entry matches: the
+     * cgroup itself or any slice, service or scope it is nested in, so
+     * protecting a slice covers everything below it. Entries are globs
+     * ("user@*.service"); ones containing '/' are matched against the whole
+     * path and each of its ancestors instead ("/user.slice/user-1000.slice"). */
+    pub fn protected_by(&self, protected: &ProtectedUnits) -> Option<String> {
+        for entry in &protected.entries {
+            match entry {
+                UnitMatch::Path(pat) => {
+                    if let Some(ancestor) = self.ancestors().into_iter().find(|a| pat.matches_with(a, PATH_MATCH)) {
+                        return Some(ancestor);
+                    }
+                }
+                UnitMatch::Component(pat) => {
+                    if let Some(c) = self.raw_path.split('/').find(|c| !c.is_empty() && pat.matches(c)) {
+                        return Some(c.to_string());
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /* "/a", "/a/b", ... up to the path itself; none for the root cgroup */
+    fn ancestors(&self) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        for c in self.raw_path.split('/').filter(|c| !c.is_empty()) {
+            let parent = out.last().map(String::as_str).unwrap_or("");
+            out.push(format!("{}/{}", parent, c));
+        }
+        out
+    }
+
+    /* Attach the first `slice_classes` entry that matches. init.scope
+     * keeps its own priority whatever the globs say. */
+    pub fn with_classes(mut self, classes: &SliceClasses) -> Self {
+        if self.slice == CgroupSlice::Init {
+            return self;
+        }
+        let ancestors = self.ancestors();
+        let found = classes
+            .entries
+            .iter()
+            .find(|(pat, _)| ancestors.iter().any(|a| pat.matches_with(a, PATH_MATCH)));
+        self.class = found.map(|(_, class)| class.clone());
+        self
+    }
+
+    /* Ranking priority: the class's if there is one, else the slice's */
+    pub fn priority(&self) -> i32 {
+        self.class.as_ref().map(|c| c.priority).unwrap_or_else(|| self.slice.priority_score())
+    }
+
+    /* Class name if classed, else the slice name */
+    pub fn class_name(&self) -> &str {
+        self.class.as_ref().map(|c| c.name.as_str()).unwrap_or_else(|| self.slice.name())
+    }
+
+    /* Location of this cgroup on disk. Callers check for the interface
+     * file they need, which is what makes v2-only actions fall back. */
+    pub fn fs_path(&self) -> PathBuf {
+        self.fs_path_in(hierarchy())
+    }
+
+    pub fn fs_path_in(&self, hierarchy: Hierarchy) -> PathBuf {
+        hierarchy.mount().join(self.raw_path.trim_start_matches('/'))
+    }
+
+    /* Read a single-value interface file such as memory.current */
+    pub fn read_value(&self, file: &str) -> Result<String> {
+        let path = self.fs_path().join(file);
+        let content = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        Ok(content.trim().to_string())
+    }
+
+    /* Why this cgroup must be left alone entirely, if at all: init, the
+     * root cgroup and protected units. Enough for memory.reclaim, which
+     * does no harm anywhere else. */
+    pub fn unit_veto(&self, protected: &ProtectedUnits) -> Option<String> {
+        if self.slice == CgroupSlice::Init {
+            return Some("init scope".into());
+        }
+        if self.raw_path.trim_matches('/').is_empty() {
+            return Some("root cgroup".into());
+        }
+        self.protected_by(protected).map(|unit| format!("protected unit {}", unit))
+    }
+
+    /* Why this cgroup must not be acted on as a whole (freeze, kill), if at all.
+     * Login session scopes hold the user's shells - including SSH - so they
+     * are only ever handled per process. `own` is sentinel's cgroup, from
+     * `own_cgroup`, read once per pass by the caller. */
+    pub fn whole_cgroup_veto(&self, protected: &ProtectedUnits, own: Option<&str>) -> Option<String> {
+        if let Some(why) = self.unit_veto(protected) {
+            return Some(why);
+        }
         if let Some(ref unit) = self.unit_name {
-            protected_units.iter().any(|protected| unit == protected)
-        } else {
-            false
+            if unit.starts_with("session-") && unit.ends_with(".scope") {
+                return Some(format!("login session {}", unit));
+            }
+        }
+        if let Some(own) = own {
+            let prefix = format!("{}/", self.raw_path.trim_end_matches('/'));
+            if own == self.raw_path || own.starts_with(&prefix) {
+                return Some("contains sentinel itself".into());
+            }
         }
+        None
     }
 }
 
+/* The cgroup sentinel itself runs in */
+pub fn own_cgroup() -> Option<String> {
+    CgroupInfo::for_pid(std::process::id()).ok().map(|cg| cg.raw_path)
+}
+
 pub fn get_slice_stats() -> Result<HashMap<CgroupSlice, usize>> {
     let mut stats = HashMap::new();
     
@@ -115,6 +428,10 @@ pub fn get_slice_stats() -> Result<HashMap<CgroupSlice, usize>> {
 mod tests {
     use super::*;
 
+    fn units(entries: &[&str]) -> ProtectedUnits {
+        ProtectedUnits::new(&entries.iter().map(|e| e.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
     #[test]
     fn test_parse_cgroup_user_slice() {
         let sample = "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox.scope";
@@ -134,18 +451,168 @@ mod tests {
         assert_eq!(info.unit_name, Some("sshd.service".to_string()));
     }
 
+    #[test]
+    fn test_parse_cgroup_v1_and_hybrid() {
+        // Legacy: no unified line; systemd's named hierarchy has the unit
+        let legacy = "12:memory:/system.slice/postgresql.service\n\
+                      4:cpu,cpuacct:/system.slice/postgresql.service\n\
+                      1:name=systemd:/system.slice/postgresql.service\n";
+        let info = CgroupInfo::parse(legacy).unwrap();
+        assert_eq!(info.slice, CgroupSlice::System);
+        assert_eq!(info.unit_name.as_deref(), Some("postgresql.service"));
+
+        // Hybrid: the unified line mirrors systemd's tree
+        let hybrid = "10:memory:/user.slice/user-1000.slice/session-2.scope\n\
+                      1:name=systemd:/user.slice/user-1000.slice/session-2.scope\n\
+                      0::/user.slice/user-1000.slice/session-2.scope\n";
+        let info = CgroupInfo::parse(hybrid).unwrap();
+        assert_eq!(info.slice, CgroupSlice::User);
+        assert_eq!(info.raw_path, "/user.slice/user-1000.slice/session-2.scope");
+
+        // Only the memory controller knows: "0::/" is not taken at face value
+        let container = "9:name=systemd:/\n4:memory:/machine.slice/machine-web.scope\n0::/\n";
+        let info = CgroupInfo::parse(container).unwrap();
+        assert_eq!(info.slice, CgroupSlice::Machine);
+
+        // "10:..." must not be mistaken for the unified line
+        let info = CgroupInfo::parse("10:memory:/system.slice/x.service\n").unwrap();
+        assert_eq!(info.raw_path, "/system.slice/x.service");
+        assert_eq!(CgroupInfo::parse("0::/\n").unwrap().raw_path, "/");
+    }
+
+    #[test]
+    fn test_detect_hierarchy() {
+        let root = std::env::temp_dir().join(format!("sentinel-cgroup-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        assert_eq!(Hierarchy::detect_at(&root), Hierarchy::Unavailable);
+        fs::create_dir_all(root.join("memory")).unwrap();
+        assert_eq!(Hierarchy::detect_at(&root), Hierarchy::Legacy);
+        fs::create_dir_all(root.join("unified")).unwrap();
+        fs::write(root.join("unified/cgroup.controllers"), "").unwrap();
+        assert_eq!(Hierarchy::detect_at(&root), Hierarchy::Hybrid);
+        fs::write(root.join("cgroup.controllers"), "memory pids").unwrap();
+        assert_eq!(Hierarchy::detect_at(&root), Hierarchy::Unified);
+        fs::remove_dir_all(&root).unwrap();
+        assert!(Hierarchy::Hybrid.limitations().is_some());
+        assert!(Hierarchy::Unified.limitations().is_none());
+    }
+
     #[test]
     fn test_is_protected() {
         let sample = "0::/system.slice/sshd.service";
         let info = CgroupInfo::parse(sample).unwrap();
         
-        let protected = vec!["sshd.service".to_string(), "sentinel.service".to_string()];
+        let protected = units(&["sshd.service", "sentinel.service"]);
         assert!(info.is_protected(&protected));
         
-        let not_protected = vec!["sentinel.service".to_string()];
+        let not_protected = units(&["sentinel.service"]);
         assert!(!info.is_protected(&not_protected));
     }
 
+    #[test]
+    fn test_protection_covers_nested_cgroups() {
+        let app = CgroupInfo::for_path("/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox.scope");
+        let other = CgroupInfo::for_path("/user.slice/user-1001.slice/user@1001.service/app.slice/app-gimp.scope");
+        let db = CgroupInfo::for_path("/system.slice/postgresql.service");
+
+        let slice = units(&["system.slice"]);
+        assert!(db.is_protected(&slice));
+        assert!(!app.is_protected(&slice));
+
+        let managers = units(&["user@*.service"]);
+        assert_eq!(app.protected_by(&managers), Some("user@1000.service".into()));
+        assert!(other.is_protected(&managers));
+        assert!(!db.is_protected(&managers));
+
+        let one_user = units(&["/user.slice/user-1000.slice"]);
+        assert_eq!(app.protected_by(&one_user), Some("/user.slice/user-1000.slice".into()));
+        assert!(!other.is_protected(&one_user));
+        // '*' stops at '/' in path entries
+        assert!(!app.is_protected(&units(&["/user.slice/*/app.slice"])));
+        assert!(app.is_protected(&units(&["/user.slice/*/*/app.slice"])));
+
+        let veto = app.whole_cgroup_veto(&managers, None).unwrap();
+        assert_eq!(veto, "protected unit user@1000.service");
+    }
+
+    #[test]
+    fn test_whole_cgroup_veto() {
+        let protected = units(&["sshd.service"]);
+
+        let app = CgroupInfo::parse("0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox.scope").unwrap();
+        assert!(app.whole_cgroup_veto(&protected, None).is_none());
+        assert_eq!(
+            app.fs_path_in(Hierarchy::Unified),
+            PathBuf::from("/sys/fs/cgroup/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox.scope")
+        );
+        assert_eq!(
+            app.fs_path_in(Hierarchy::Hybrid),
+            PathBuf::from("/sys/fs/cgroup/unified/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox.scope")
+        );
+
+        let sshd = CgroupInfo::parse("0::/system.slice/sshd.service").unwrap();
+        assert!(sshd.whole_cgroup_veto(&protected, None).is_some());
+
+        let init = CgroupInfo::parse("0::/init.scope").unwrap();
+        assert!(init.whole_cgroup_veto(&protected, None).is_some());
+
+        let session = CgroupInfo::parse("0::/user.slice/user-1000.slice/session-3.scope").unwrap();
+        assert!(session.whole_cgroup_veto(&protected, None).is_some());
+        // Only the whole-cgroup actions spare sessions
+        assert!(session.unit_veto(&protected).is_none());
+        assert!(sshd.unit_veto(&protected).is_some());
+
+        let root = CgroupInfo::parse("0::/").unwrap();
+        assert!(root.whole_cgroup_veto(&protected, None).is_some());
+
+        let own = Some("/system.slice/sentinel.service");
+        assert!(CgroupInfo::parse("0::/system.slice").unwrap().whole_cgroup_veto(&protected, own).is_some());
+        assert!(CgroupInfo::parse("0::/system.slice/sentinel.service").unwrap().whole_cgroup_veto(&protected, own).is_some());
+        assert!(CgroupInfo::parse("0::/system.slice/sentinel").unwrap().whole_cgroup_veto(&protected, own).is_none());
+    }
+
+    #[test]
+    fn test_slice_classes() {
+        let classes = SliceClasses::new(&[
+            SliceClass { pattern: "/batch.slice".into(), class: "batch".into(), priority: 150 },
+            SliceClass { pattern: "/user.slice/*/user@*.service/app.slice/ci-*.scope".into(), class: "ci".into(), priority: 120 },
+        ])
+        .unwrap();
+        let job = CgroupInfo::for_path("/batch.slice/nightly-report.service").with_classes(&classes);
+        assert_eq!(job.class_name(), "batch");
+        assert_eq!(job.priority(), 150);
+        assert_eq!(job.slice, CgroupSlice::Unknown);
+        // The unit is still found
+        assert_eq!(job.unit_name.as_deref(), Some("nightly-report.service"));
+
+        let ci = CgroupInfo::for_path("/user.slice/user-1000.slice/user@1000.service/app.slice/ci-runner.scope")
+            .with_classes(&classes);
+        assert_eq!(ci.class_name(), "ci");
+        // Still a user session for reclaim and the whole-cgroup checks
+        assert_eq!(ci.slice, CgroupSlice::User);
+        assert!(crate::reclaim::eligible(&ci, &[]));
+
+        let app = CgroupInfo::for_path("/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox.scope")
+            .with_classes(&classes);
+        assert_eq!(app.class, None);
+        assert_eq!(app.class_name(), "user");
+        // Prefix only at a component boundary
+        let other = CgroupInfo::for_path("/batch.slice2/x.service").with_classes(&classes);
+        assert_eq!(other.class, None);
+
+        // A catch-all class never reaches init.scope or lifts its veto
+        let everything = SliceClasses::new(&[SliceClass { pattern: "/*".into(), class: "all".into(), priority: 500 }]).unwrap();
+        let init = CgroupInfo::for_path("/init.scope").with_classes(&everything);
+        assert_eq!(init.class, None);
+        assert_eq!(init.priority(), 0);
+        assert_eq!(init.whole_cgroup_veto(&ProtectedUnits::default(), None), Some("init scope".into()));
+
+        // Bad entries fail the config load
+        assert!(SliceClasses::new(&[SliceClass { pattern: "/[".into(), class: "x".into(), priority: 1 }]).is_err());
+        assert!(SliceClasses::new(&[SliceClass { pattern: "/x".into(), class: String::new(), priority: 1 }]).is_err());
+    }
+
     #[test]
     fn test_slice_priority() {
         assert!(CgroupSlice::User.priority_score() > CgroupSlice::System.priority_score());
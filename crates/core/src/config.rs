@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
 use std::{fs, path::Path};
 
+use crate::cgroups::{ProtectedUnits, SliceClass, SliceClasses};
+use crate::exclude::{self, Exclusions};
+use crate::ladder::{LadderAction, Rung};
+use crate::policy::PressureState;
+use crate::procinfo::BadnessMode;
+use crate::rules::{Rule, RuleSet};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CliUi {
     pub color: Option<String>,
@@ -16,6 +23,8 @@ pub struct Config {
     pub hard_threshold_pct: u8,
     pub mode: String,
     pub scan_interval_sec: u64,
+    /* Never candidates: exact comm, or "exe:", "path:", "glob:" and
+     * "regex:" entries (see `exclude`) */
     pub exclude_names: Vec<String>,
     pub max_actions_per_min: u32,
     pub cli: Option<CliUi>,
@@ -26,13 +35,165 @@ pub struct Config {
     pub psi_soft_pct: f64,
     #[serde(default = "default_psi_hard_pct")]
     pub psi_hard_pct: f64,
+    /* Custom cgroup classes and their priority, first match wins */
+    #[serde(default)]
+    pub slice_classes: Vec<SliceClass>,
+
+    /* Unit or slice globs; protecting a slice protects everything under it */
     #[serde(default)]
     pub protected_units: Vec<String>,
+
+    /* Escalation ladder, in order. Empty means the default ladder for `mode`. */
+    #[serde(default)]
+    pub ladder: Vec<Rung>,
+
+    /* Hysteresis: to step down, avail% (and swap free%) must clear the
+     * threshold by `hysteresis_pct` and PSI must drop `hysteresis_psi_pct`
+     * below it, after at least the dwell time in the current state */
+    #[serde(default = "default_hysteresis_pct")]
+    pub hysteresis_pct: u8,
+    #[serde(default = "default_hysteresis_psi_pct")]
+    pub hysteresis_psi_pct: f64,
+    #[serde(default = "default_dwell_soft_sec")]
+    pub dwell_soft_sec: u64,
+    #[serde(default = "default_dwell_hard_sec")]
+    pub dwell_hard_sec: u64,
+
+    /* Predict when MemAvailable plus usable swap runs out from the trend over
+     * the last `trend_window_sec`, and enter Soft / Hard early when that is
+     * within the matching horizon */
+    #[serde(default = "default_trend_enabled")]
+    pub trend_enabled: bool,
+    #[serde(default = "default_trend_window_sec")]
+    pub trend_window_sec: u64,
+    #[serde(default = "default_trend_soft_horizon_sec")]
+    pub trend_soft_horizon_sec: u64,
+    #[serde(default = "default_trend_hard_horizon_sec")]
+    pub trend_hard_horizon_sec: u64,
+
+    /* Percent of SwapTotal still free below which swap counts as soft / hard
+     * pressure. Ignored on boxes without swap. */
+    #[serde(default = "default_swap_soft_threshold_pct")]
+    pub swap_soft_threshold_pct: u8,
+    #[serde(default = "default_swap_hard_threshold_pct")]
+    pub swap_hard_threshold_pct: u8,
+    /* Treat exhausted swap as hard pressure only while MemAvailable or PSI
+     * show soft pressure too; off, it is hard on its own */
+    #[serde(default)]
+    pub swap_hard_needs_mem_pressure: bool,
+
+    /* Badness added per percent of memory.pressure some_avg10 in the
+     * candidate's own cgroup, so the unit that is actually stalling goes first */
+    #[serde(default = "default_cgroup_psi_weight")]
+    pub cgroup_psi_weight: f64,
+
+    /* Kernel PSI trigger: wake up as soon as `psi_trigger_stall_ms` of "some"
+     * stall accumulates within `psi_trigger_window_ms`, rather than waiting
+     * for the next scan. Changes take effect on restart. */
+    #[serde(default = "default_psi_trigger_enabled")]
+    pub psi_trigger_enabled: bool,
+    #[serde(default = "default_psi_trigger_stall_ms")]
+    pub psi_trigger_stall_ms: u64,
+    #[serde(default = "default_psi_trigger_window_ms")]
+    pub psi_trigger_window_ms: u64,
+
+    /* mlock the reserve balloon so it can't be swapped out (needs CAP_IPC_LOCK) */
+    #[serde(default = "default_reserve_mlock")]
+    pub reserve_mlock: bool,
+
+    /* The reserve is released and re-inflated in this many chunks; under soft
+     * pressure chunks are given back in proportion to how deep it is */
+    #[serde(default = "default_reserve_chunks")]
+    pub reserve_chunks: u32,
+    /* Re-inflate only while avail% stays this far above the soft threshold
+     * after taking the chunk, at most one chunk per interval */
+    #[serde(default = "default_reserve_refill_margin_pct")]
+    pub reserve_refill_margin_pct: f64,
+    #[serde(default = "default_reserve_refill_interval_sec")]
+    pub reserve_refill_interval_sec: u64,
+
+    /* Soft pressure: ask user/machine slices and `reclaim_units` to give memory
+     * back through memory.reclaim, at most `reclaim_step_pct` percent of a
+     * group's usage per request, until avail% is out of soft pressure */
+    #[serde(default = "default_reclaim_enabled")]
+    pub reclaim_enabled: bool,
+    #[serde(default)]
+    pub reclaim_units: Vec<String>,
+    #[serde(default = "default_reclaim_step_pct")]
+    pub reclaim_step_pct: u8,
+
+    /* Soft pressure: lower memory.high on the worst cgroup to this percent of its usage */
+    #[serde(default = "default_soft_throttle_enabled")]
+    pub soft_throttle_enabled: bool,
+    #[serde(default = "default_soft_throttle_pct")]
+    pub soft_throttle_pct: u8,
+
+    /* "sentinel" (memory freed, oom_score_adj, cgroup priority and stall),
+     * "kernel" (/proc/<pid>/oom_score as is) or "hybrid" (oom_score plus
+     * cgroup priority and stall) */
+    #[serde(default = "default_badness_mode")]
+    pub badness_mode: String,
+
+    /* Badness adjustments for matching processes, applied in order */
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+
+    /* Rank whole process subtrees (e.g. `make` and its compilers) on their
+     * combined memory instead of single processes; pair with
+     * kill_granularity = "process-tree" */
+    #[serde(default)]
+    pub subtree_badness: bool,
+
+    /* "process", "process-tree" (victim and its descendants) or "cgroup" (every task in the victim's cgroup) */
+    #[serde(default = "default_kill_granularity")]
+    pub kill_granularity: String,
+
+    /* "cgroup" freezes the victim's whole cgroup via cgroup.freeze, "process" only its PID */
+    #[serde(default = "default_freeze_granularity")]
+    pub freeze_granularity: String,
+
+    /* Hybrid mode: kill a frozen process if pressure is still hard this long after freezing it */
+    #[serde(default = "default_freeze_escalate_sec")]
+    pub freeze_escalate_sec: u64,
+
+    /* Extra action budget usable only while PSI full_avg10 is at or above this */
+    #[serde(default = "default_emergency_psi_full_pct")]
+    pub emergency_psi_full_pct: f64,
+    #[serde(default = "default_emergency_max_actions_per_min")]
+    pub emergency_max_actions_per_min: u32,
 }
 
 fn default_psi_enabled() -> bool { true }
 fn default_psi_soft_pct() -> f64 { 10.0 }
 fn default_psi_hard_pct() -> f64 { 30.0 }
+fn default_hysteresis_pct() -> u8 { 5 }
+fn default_hysteresis_psi_pct() -> f64 { 3.0 }
+fn default_dwell_soft_sec() -> u64 { 10 }
+fn default_dwell_hard_sec() -> u64 { 5 }
+fn default_trend_enabled() -> bool { true }
+fn default_trend_window_sec() -> u64 { 30 }
+fn default_trend_soft_horizon_sec() -> u64 { 60 }
+fn default_trend_hard_horizon_sec() -> u64 { 15 }
+fn default_swap_soft_threshold_pct() -> u8 { 20 }
+fn default_swap_hard_threshold_pct() -> u8 { 5 }
+fn default_cgroup_psi_weight() -> f64 { 10.0 }
+fn default_psi_trigger_enabled() -> bool { true }
+fn default_psi_trigger_stall_ms() -> u64 { 150 }
+fn default_psi_trigger_window_ms() -> u64 { 1000 }
+fn default_reserve_mlock() -> bool { true }
+fn default_reserve_chunks() -> u32 { 8 }
+fn default_reserve_refill_margin_pct() -> f64 { 5.0 }
+fn default_reserve_refill_interval_sec() -> u64 { 10 }
+fn default_reclaim_enabled() -> bool { true }
+fn default_reclaim_step_pct() -> u8 { 10 }
+fn default_soft_throttle_enabled() -> bool { true }
+fn default_soft_throttle_pct() -> u8 { 90 }
+fn default_badness_mode() -> String { "sentinel".into() }
+fn default_kill_granularity() -> String { "process".into() }
+fn default_freeze_granularity() -> String { "cgroup".into() }
+fn default_freeze_escalate_sec() -> u64 { 10 }
+fn default_emergency_psi_full_pct() -> f64 { 40.0 }
+fn default_emergency_max_actions_per_min() -> u32 { 2 }
 
 impl Default for Config {
     fn default() -> Self {
@@ -42,13 +203,53 @@ impl Default for Config {
             hard_threshold_pct: 5,
             mode: "hybrid".into(),
             scan_interval_sec: 2,
-            exclude_names: vec!["sshd".into(), "systemd".into(), "sentinel".into()],
+            exclude_names: vec![
+                "sshd".into(),
+                "sshd-session".into(),
+                "systemd".into(),
+                "glob:systemd-*".into(),
+                "sentinel".into(),
+            ],
             max_actions_per_min: 4,
             cli: Some(CliUi { color: Some("auto".into()), unicode: Some("auto".into()), table_max_width: Some(120) }),
             psi_enabled: true,
             psi_soft_pct: 10.0,
             psi_hard_pct: 30.0,
+            slice_classes: Vec::new(),
             protected_units: vec!["sshd.service".into(), "sentinel.service".into(), "ssh.service".into()],
+            ladder: Vec::new(),
+            hysteresis_pct: default_hysteresis_pct(),
+            hysteresis_psi_pct: default_hysteresis_psi_pct(),
+            dwell_soft_sec: default_dwell_soft_sec(),
+            dwell_hard_sec: default_dwell_hard_sec(),
+            trend_enabled: default_trend_enabled(),
+            trend_window_sec: default_trend_window_sec(),
+            trend_soft_horizon_sec: default_trend_soft_horizon_sec(),
+            trend_hard_horizon_sec: default_trend_hard_horizon_sec(),
+            swap_soft_threshold_pct: default_swap_soft_threshold_pct(),
+            swap_hard_threshold_pct: default_swap_hard_threshold_pct(),
+            swap_hard_needs_mem_pressure: false,
+            cgroup_psi_weight: default_cgroup_psi_weight(),
+            psi_trigger_enabled: default_psi_trigger_enabled(),
+            psi_trigger_stall_ms: default_psi_trigger_stall_ms(),
+            psi_trigger_window_ms: default_psi_trigger_window_ms(),
+            reserve_mlock: default_reserve_mlock(),
+            reserve_chunks: default_reserve_chunks(),
+            reserve_refill_margin_pct: default_reserve_refill_margin_pct(),
+            reserve_refill_interval_sec: default_reserve_refill_interval_sec(),
+            reclaim_enabled: default_reclaim_enabled(),
+            reclaim_units: Vec::new(),
+            reclaim_step_pct: default_reclaim_step_pct(),
+            soft_throttle_enabled: default_soft_throttle_enabled(),
+            soft_throttle_pct: default_soft_throttle_pct(),
+            badness_mode: default_badness_mode(),
+            rules: Vec::new(),
+            subtree_badness: false,
+            kill_granularity: default_kill_granularity(),
+            freeze_granularity: default_freeze_granularity(),
+            freeze_escalate_sec: default_freeze_escalate_sec(),
+            emergency_psi_full_pct: default_emergency_psi_full_pct(),
+            emergency_max_actions_per_min: default_emergency_max_actions_per_min(),
         }
     }
 }
@@ -57,6 +258,61 @@ impl Config {
     pub fn load_from(path: &Path) -> Result<Self> {
         let s = fs::read_to_string(path).with_context(|| format!("reading {:?}", path))?;
         let cfg: Self = toml::from_str(&s).with_context(|| "parsing TOML config")?;
+        cfg.validate()?;
         Ok(cfg)
     }
+
+    pub fn validate(&self) -> Result<()> {
+        // A typo here would quietly act per process
+        one_of("mode", &self.mode, &["watch", "slow", "kill", "hybrid"])?;
+        one_of("kill_granularity", &self.kill_granularity, &["process", "process-tree", "cgroup"])?;
+        one_of("freeze_granularity", &self.freeze_granularity, &["process", "cgroup"])?;
+        if BadnessMode::parse(&self.badness_mode).is_none() {
+            bail!("badness_mode must be \"sentinel\", \"kernel\" or \"hybrid\", not {:?}", self.badness_mode);
+        }
+        // With the margin at or past the soft threshold, the level needed
+        // to step down can never be reached and Soft is never left
+        if !(self.hysteresis_psi_pct.is_finite() && self.hysteresis_psi_pct >= 0.0 && self.hysteresis_psi_pct < self.psi_soft_pct) {
+            bail!("hysteresis_psi_pct must be at least 0 and below psi_soft_pct ({}), not {}", self.psi_soft_pct, self.hysteresis_psi_pct);
+        }
+        if self.soft_threshold_pct as u16 + self.hysteresis_pct as u16 >= 100 {
+            bail!("soft_threshold_pct + hysteresis_pct must be below 100, not {}",
+                  self.soft_threshold_pct as u16 + self.hysteresis_pct as u16);
+        }
+        if !self.cgroup_psi_weight.is_finite() || self.cgroup_psi_weight < 0.0 {
+            bail!("cgroup_psi_weight must be a non-negative number, not {}", self.cgroup_psi_weight);
+        }
+        Exclusions::new(&self.exclude_names)?;
+        ProtectedUnits::new(&self.protected_units)?;
+        RuleSet::new(&self.rules)?;
+        SliceClasses::new(&self.slice_classes)?;
+        for (i, rung) in self.ladder.iter().enumerate() {
+            if rung.when == PressureState::Healthy {
+                bail!("ladder rung {} ({}): `when` must be \"soft\" or \"hard\"", i + 1, rung.action);
+            }
+            // At 0 the rung repeats every tick, signalling a victim that is
+            // still shutting down and spending the action budget on it
+            if matches!(rung.action, LadderAction::Term | LadderAction::Kill) && rung.timeout_sec == 0 {
+                bail!("ladder rung {} ({}): `timeout_sec` must be at least 1", i + 1, rung.action);
+            }
+        }
+        Ok(())
+    }
+
+    /* Settings that load but probably don't do what was meant */
+    pub fn warnings(&self) -> Vec<String> {
+        let mut out = exclude::warnings(&self.exclude_names, &exclude::running());
+        if self.subtree_badness && self.kill_granularity == "process" {
+            out.push("subtree_badness ranks whole subtrees, but kill_granularity = \"process\" only signals the head \
+                      (e.g. `make`, not its compilers); set kill_granularity = \"process-tree\"".into());
+        }
+        out
+    }
+}
+
+fn one_of(field: &str, value: &str, allowed: &[&str]) -> Result<()> {
+    if !allowed.contains(&value) {
+        bail!("{} must be one of {}, not {:?}", field, allowed.join(", "), value);
+    }
+    Ok(())
 }
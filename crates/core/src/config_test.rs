@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use crate::config::Config;
+    use crate::exclude;
     use std::fs::File;
     use std::io::Write;
     use std::path::Path;
@@ -17,7 +18,7 @@ mod tests {
             max_actions_per_min = 2
         "#;
         let path = Path::new("/tmp/test_memsentinel.toml");
-        let mut file = File::create(&path).unwrap();
+        let mut file = File::create(path).unwrap();
         file.write_all(toml.as_bytes()).unwrap();
         let cfg = Config::load_from(path).unwrap();
         assert_eq!(cfg.reserve_mb, 256);
@@ -27,5 +28,205 @@ mod tests {
         assert_eq!(cfg.scan_interval_sec, 1);
         assert_eq!(cfg.exclude_names, vec!["sshd", "systemd"]);
         assert_eq!(cfg.max_actions_per_min, 2);
+        assert!(cfg.ladder.is_empty());
+        assert_eq!(cfg.hysteresis_pct, 5);
+        assert_eq!(cfg.dwell_soft_sec, 10);
+        assert!(cfg.trend_enabled);
+        assert_eq!(cfg.trend_soft_horizon_sec, 60);
+        assert_eq!(cfg.trend_hard_horizon_sec, 15);
+        assert_eq!(cfg.swap_soft_threshold_pct, 20);
+        assert_eq!(cfg.swap_hard_threshold_pct, 5);
+        assert!(!cfg.swap_hard_needs_mem_pressure);
+        assert_eq!(cfg.cgroup_psi_weight, 10.0);
+        assert!(cfg.psi_trigger_enabled);
+        assert_eq!(cfg.psi_trigger_stall_ms, 150);
+        assert_eq!(cfg.psi_trigger_window_ms, 1000);
+        assert!(cfg.reserve_mlock);
+        assert_eq!(cfg.reserve_chunks, 8);
+        assert_eq!(cfg.reserve_refill_interval_sec, 10);
+        assert!(cfg.reclaim_enabled);
+        assert!(cfg.reclaim_units.is_empty());
+        assert_eq!(cfg.reclaim_step_pct, 10);
+        assert!(cfg.soft_throttle_enabled);
+        assert_eq!(cfg.soft_throttle_pct, 90);
+        assert_eq!(cfg.badness_mode, "sentinel");
+        assert!(cfg.rules.is_empty());
+        assert!(cfg.slice_classes.is_empty());
+        assert!(!cfg.subtree_badness);
+        assert_eq!(cfg.kill_granularity, "process");
+        assert_eq!(cfg.freeze_granularity, "cgroup");
+        assert_eq!(cfg.freeze_escalate_sec, 10);
+    }
+
+    #[test]
+    fn test_config_ladder() {
+        use crate::ladder::LadderAction;
+        use crate::policy::PressureState;
+
+        let base = r#"
+            reserve_mb = 256
+            soft_threshold_pct = 10
+            hard_threshold_pct = 3
+            mode = "kill"
+            scan_interval_sec = 1
+            exclude_names = []
+            max_actions_per_min = 2
+        "#;
+        let toml = format!("{}{}", base, r#"
+            [[ladder]]
+            action = "release-reserve"
+            when = "soft"
+
+            [[ladder]]
+            action = "term"
+            timeout_sec = 5
+
+            [[ladder]]
+            action = "kill"
+            when = "hard"
+            timeout_sec = 5
+        "#);
+        let path = Path::new("/tmp/test_memsentinel_ladder.toml");
+        File::create(path).unwrap().write_all(toml.as_bytes()).unwrap();
+        let cfg = Config::load_from(path).unwrap();
+        assert_eq!(cfg.ladder.len(), 3);
+        assert_eq!(cfg.ladder[0].when, PressureState::Soft);
+        assert_eq!(cfg.ladder[1].action, LadderAction::Term);
+        // `when` defaults to hard
+        assert_eq!(cfg.ladder[1].when, PressureState::Hard);
+        assert_eq!(cfg.ladder[1].timeout_sec, 5);
+
+        let bad = format!("{}{}", base, "[[ladder]]\naction = \"freeze\"\nwhen = \"healthy\"\n");
+        File::create(path).unwrap().write_all(bad.as_bytes()).unwrap();
+        assert!(Config::load_from(path).is_err());
+
+        // term and kill need time for the victim to exit
+        let bad = format!("{}{}", base, "[[ladder]]\naction = \"term\"\n");
+        File::create(path).unwrap().write_all(bad.as_bytes()).unwrap();
+        assert!(Config::load_from(path).is_err());
+    }
+
+    #[test]
+    fn test_config_rules() {
+        use crate::rules::Mark;
+
+        let base = r#"
+            reserve_mb = 256
+            soft_threshold_pct = 10
+            hard_threshold_pct = 3
+            mode = "kill"
+            scan_interval_sec = 1
+            exclude_names = []
+            max_actions_per_min = 2
+        "#;
+        let toml = format!("{}{}", base, r#"
+            [[rules]]
+            name = "builds"
+            cmdline = "^(cc1|cc1plus|rustc) "
+            multiplier = 3.0
+
+            [[rules]]
+            cgroup = "/system.slice/postgresql.service"
+            mark = "never"
+        "#);
+        let path = Path::new("/tmp/test_memsentinel_rules.toml");
+        File::create(path).unwrap().write_all(toml.as_bytes()).unwrap();
+        let cfg = Config::load_from(path).unwrap();
+        assert_eq!(cfg.rules.len(), 2);
+        assert_eq!(cfg.rules[0].multiplier, Some(3.0));
+        assert_eq!(cfg.rules[1].mark, Some(Mark::Never));
+
+        // Broken regexes fail the load rather than never matching
+        let bad = format!("{}{}", base, "[[rules]]\ncmdline = \"(\"\noffset = 10\n");
+        File::create(path).unwrap().write_all(bad.as_bytes()).unwrap();
+        assert!(Config::load_from(path).is_err());
+    }
+
+    #[test]
+    fn test_config_exclude_names() {
+        let mut cfg = Config::default();
+        assert!(cfg.validate().is_ok());
+        // The test binary's own comm starts with "sentinel", so leave out running processes
+        assert!(exclude::warnings(&cfg.exclude_names, &[]).is_empty());
+
+        cfg.exclude_names = vec!["regex:[".into()];
+        assert!(cfg.validate().is_err());
+        cfg.exclude_names = vec!["evolution-alarm-notify".into()];
+        assert!(cfg.validate().is_ok());
+        assert_eq!(cfg.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_config_protected_units() {
+        let mut cfg = Config {
+            protected_units: vec!["system.slice".into(), "user@*.service".into()],
+            ..Config::default()
+        };
+        assert!(cfg.validate().is_ok());
+        cfg.protected_units = vec!["user@[.service".into()];
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn test_config_slice_classes() {
+        let toml = r#"
+            reserve_mb = 256
+            soft_threshold_pct = 10
+            hard_threshold_pct = 3
+            mode = "kill"
+            scan_interval_sec = 1
+            exclude_names = []
+            max_actions_per_min = 2
+
+            [[slice_classes]]
+            match = "/batch.slice"
+            class = "batch"
+            priority = 150
+        "#;
+        let path = Path::new("/tmp/test_memsentinel_classes.toml");
+        File::create(path).unwrap().write_all(toml.as_bytes()).unwrap();
+        let cfg = Config::load_from(path).unwrap();
+        assert_eq!(cfg.slice_classes.len(), 1);
+        assert_eq!(cfg.slice_classes[0].pattern, "/batch.slice");
+        assert_eq!(cfg.slice_classes[0].priority, 150);
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_modes() {
+        let mut cfg = Config { kill_granularity: "process_tree".into(), ..Config::default() };
+        assert!(cfg.validate().is_err());
+        cfg.kill_granularity = "process-tree".into();
+        cfg.freeze_granularity = "cgroups".into();
+        assert!(cfg.validate().is_err());
+        cfg.freeze_granularity = "process".into();
+        cfg.mode = "agressive".into();
+        assert!(cfg.validate().is_err());
+        cfg.mode = "watch".into();
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_rejects_unreachable_hysteresis() {
+        let mut cfg = Config { hysteresis_psi_pct: 10.0, ..Config::default() };
+        assert!(cfg.validate().is_err());
+        cfg.hysteresis_psi_pct = f64::NAN;
+        assert!(cfg.validate().is_err());
+        cfg.hysteresis_psi_pct = 3.0;
+        cfg.hysteresis_pct = 85;
+        assert!(cfg.validate().is_err());
+        cfg.hysteresis_pct = 5;
+        cfg.cgroup_psi_weight = f64::INFINITY;
+        assert!(cfg.validate().is_err());
+        cfg.cgroup_psi_weight = 0.0;
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_subtree_granularity_warning() {
+        let warns = |cfg: &Config| cfg.warnings().iter().any(|w| w.contains("subtree_badness"));
+        let mut cfg = Config { subtree_badness: true, ..Config::default() };
+        assert!(warns(&cfg));
+        cfg.kill_granularity = "process-tree".into();
+        assert!(!warns(&cfg));
     }
 }
@@ -0,0 +1,287 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::policy::{PressureState, Transition};
+
+/* Local control protocol between sentinelctl and the running daemon.
+ * One JSON object per line: the client sends a RequestEnvelope, the daemon
+ * answers with a single ResponseEnvelope and closes the connection. */
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const SOCKET_PATH: &str = "/var/run/sentinel.sock";
+
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    ReserveHold,
+    ReserveRelease,
+    ReserveRebuild,
+    Reload,
+    Config,
+    LastDecision,
+}
+
+impl Request {
+    /* Requests that change daemon state - only root may send these */
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Request::ReserveHold | Request::ReserveRelease | Request::ReserveRebuild | Request::Reload
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Response {
+    Status(Box<DaemonStatus>),
+    Reserve {
+        held: bool,
+        size_mb: u64,
+        #[serde(default)]
+        resident_bytes: u64,
+        #[serde(default)]
+        chunks_held: usize,
+        #[serde(default)]
+        chunks_total: usize,
+    },
+    Reloaded {
+        /* The file the daemon read; absent from older daemons */
+        #[serde(default)]
+        path: Option<String>,
+    },
+    Config(Box<Config>),
+    Decision { decision: Option<Decision> },
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestEnvelope {
+    pub version: u32,
+    pub request: Request,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseEnvelope {
+    pub version: u32,
+    pub response: Response,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub version: String,
+    pub uptime_secs: u64,
+    pub mode: String,
+    pub state: PressureState,
+    #[serde(default)]
+    pub state_since_secs: u64,
+    pub avail_pct: f64,
+    pub psi_some_avg10: Option<f64>,
+    /* Effective: free zram swap counts only as far as RAM can hold it */
+    #[serde(default)]
+    pub swap_free_pct: Option<f64>,
+    /* Trend of MemAvailable plus usable swap */
+    #[serde(default)]
+    pub headroom_rate_kb_s: Option<f64>,
+    #[serde(default)]
+    pub exhaustion_secs: Option<u64>,
+    pub reserve_held: bool,
+    pub reserve_mb: u64,
+    #[serde(default)]
+    pub reserve_resident_bytes: u64,
+    #[serde(default)]
+    pub reserve_locked: bool,
+    #[serde(default)]
+    pub reserve_chunks_held: usize,
+    #[serde(default)]
+    pub reserve_chunks_total: usize,
+    pub last_decision: Option<Decision>,
+    #[serde(default)]
+    pub last_transition: Option<Transition>,
+    /* Escalation ladder rung in effect, 1-based, and for how long */
+    #[serde(default)]
+    pub ladder_rung: Option<String>,
+    #[serde(default)]
+    pub ladder_position: Option<usize>,
+    #[serde(default)]
+    pub ladder_len: usize,
+    #[serde(default)]
+    pub ladder_rung_secs: Option<u64>,
+    #[serde(default)]
+    pub frozen_pids: Vec<i32>,
+    #[serde(default)]
+    pub frozen_cgroups: Vec<String>,
+    #[serde(default)]
+    pub throttled_cgroups: Vec<String>,
+    #[serde(default)]
+    pub actions_remaining: u32,
+    #[serde(default)]
+    pub emergency_actions_remaining: u32,
+}
+
+/* What the daemon last did and why */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Decision {
+    pub at_unix: u64,
+    pub state: PressureState,
+    pub action: String,
+    pub target_pid: Option<i32>,
+    pub target_name: Option<String>,
+    pub reason: String,
+}
+
+impl Decision {
+    pub fn new(state: PressureState, action: &str, reason: impl Into<String>) -> Self {
+        let at_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            at_unix,
+            state,
+            action: action.to_string(),
+            target_pid: None,
+            target_name: None,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn with_target(mut self, pid: i32, name: &str) -> Self {
+        self.target_pid = Some(pid);
+        self.target_name = Some(name.to_string());
+        self
+    }
+}
+
+pub fn write_message<T: Serialize>(w: &mut impl Write, msg: &T) -> Result<()> {
+    let mut line = serde_json::to_string(msg)?;
+    line.push('\n');
+    w.write_all(line.as_bytes())?;
+    w.flush()?;
+    Ok(())
+}
+
+pub fn read_message<T: DeserializeOwned>(r: &mut impl BufRead) -> Result<T> {
+    let mut line = String::new();
+    if r.read_line(&mut line)? == 0 {
+        bail!("connection closed before a message was received");
+    }
+    serde_json::from_str(line.trim_end()).context("malformed control message")
+}
+
+/* Send one request to the daemon listening on the default socket */
+pub fn request(req: Request) -> Result<Response> {
+    request_at(Path::new(SOCKET_PATH), req)
+}
+
+pub fn request_at(path: &Path, req: Request) -> Result<Response> {
+    let mut stream = UnixStream::connect(path)
+        .with_context(|| format!("sentinel daemon is not reachable at {}", path.display()))?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    write_message(&mut stream, &RequestEnvelope { version: PROTOCOL_VERSION, request: req })?;
+    let env: ResponseEnvelope = read_message(&mut BufReader::new(&stream))?;
+
+    if env.version != PROTOCOL_VERSION {
+        return Err(anyhow!(
+            "daemon speaks control protocol v{}, sentinelctl expects v{}",
+            env.version,
+            PROTOCOL_VERSION
+        ));
+    }
+    Ok(env.response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_request_wire_format() {
+        let env = RequestEnvelope { version: PROTOCOL_VERSION, request: Request::ReserveRelease };
+        let mut buf = Vec::new();
+        write_message(&mut buf, &env).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            "{\"version\":1,\"request\":{\"cmd\":\"reserve_release\"}}\n"
+        );
+
+        let parsed: RequestEnvelope = read_message(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(parsed.request, Request::ReserveRelease);
+    }
+
+    #[test]
+    fn test_reloaded_path() {
+        let raw = "{\"version\":1,\"response\":{\"kind\":\"reloaded\",\"path\":\"/etc/memsentinel.toml\"}}\n";
+        let env: ResponseEnvelope = read_message(&mut Cursor::new(raw)).unwrap();
+        assert!(matches!(env.response, Response::Reloaded { path: Some(ref p) } if p == "/etc/memsentinel.toml"));
+        // Older daemons send no path
+        let raw = "{\"version\":1,\"response\":{\"kind\":\"reloaded\"}}\n";
+        let env: ResponseEnvelope = read_message(&mut Cursor::new(raw)).unwrap();
+        assert!(matches!(env.response, Response::Reloaded { path: None }));
+    }
+
+    #[test]
+    fn test_unknown_request_rejected() {
+        let raw = "{\"version\":1,\"request\":{\"cmd\":\"format_disk\"}}\n";
+        let parsed: Result<RequestEnvelope> = read_message(&mut Cursor::new(raw));
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn test_request_over_socket() {
+        let dir = std::env::temp_dir().join(format!("sentinel-ctl-test-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&dir);
+        let path = dir.join("ctl.sock");
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let env: RequestEnvelope = read_message(&mut BufReader::new(&stream)).unwrap();
+            assert_eq!(env.request, Request::ReserveHold);
+            let reply = ResponseEnvelope {
+                version: PROTOCOL_VERSION,
+                response: Response::Reserve {
+                    held: true,
+                    size_mb: 512,
+                    resident_bytes: 512 << 20,
+                    chunks_held: 8,
+                    chunks_total: 8,
+                },
+            };
+            write_message(&mut &stream, &reply).unwrap();
+        });
+
+        match request_at(&path, Request::ReserveHold).unwrap() {
+            Response::Reserve { held, size_mb, .. } => {
+                assert!(held);
+                assert_eq!(size_mb, 512);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        server.join().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_mutating_requests() {
+        assert!(Request::Reload.is_mutating());
+        assert!(Request::ReserveHold.is_mutating());
+        assert!(!Request::Status.is_mutating());
+        assert!(!Request::LastDecision.is_mutating());
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{bail, Context, Result};
+use glob::Pattern;
+use regex::Regex;
+use std::cell::OnceCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/* `exclude_names`: processes that are never candidates. An entry is a
+ * process name matched exactly against comm, or one of
+ *
+ *   comm:NAME    exact comm (same as a plain entry)
+ *   exe:NAME     basename of /proc/<pid>/exe - for names longer than comm
+ *   path:/PATH   full executable path
+ *   glob:PAT     glob on comm; on the executable path if PAT starts with /
+ *   regex:RE     regex searched in comm
+ *
+ * Entries are compiled when the config is loaded. */
+
+/* The kernel keeps at most this many characters of comm */
+pub const COMM_LEN: usize = 15;
+
+const PREFIXES: [&str; 5] = ["comm", "exe", "path", "glob", "regex"];
+
+#[derive(Debug)]
+enum NameMatch {
+    Comm(String),
+    Exe(String),
+    Path(PathBuf),
+    Glob(Pattern),
+    PathGlob(Pattern),
+    Regex(Regex),
+}
+
+/* Splits `mode:value`; anything without a known mode is a plain comm, so
+ * names like "kworker/0:1" need no escaping */
+fn split(entry: &str) -> (&str, &str) {
+    match entry.split_once(':') {
+        Some((mode, value)) if PREFIXES.contains(&mode) => (mode, value),
+        _ => ("comm", entry),
+    }
+}
+
+impl NameMatch {
+    fn parse(entry: &str) -> Result<Self> {
+        let (mode, value) = split(entry);
+        if value.is_empty() {
+            bail!("exclude_names entry {:?} is empty", entry);
+        }
+        Ok(match mode {
+            "exe" => NameMatch::Exe(value.into()),
+            "path" => {
+                if !value.starts_with('/') {
+                    bail!("exclude_names entry {:?}: path must be absolute", entry);
+                }
+                NameMatch::Path(value.into())
+            }
+            "glob" => {
+                let pat = Pattern::new(value).with_context(|| format!("exclude_names entry {:?}: invalid glob", entry))?;
+                if value.starts_with('/') { NameMatch::PathGlob(pat) } else { NameMatch::Glob(pat) }
+            }
+            "regex" => NameMatch::Regex(
+                Regex::new(value).with_context(|| format!("exclude_names entry {:?}: invalid regex", entry))?,
+            ),
+            _ => NameMatch::Comm(value.into()),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Exclusions {
+    entries: Vec<NameMatch>,
+}
+
+impl Exclusions {
+    pub fn new(entries: &[String]) -> Result<Self> {
+        let entries = entries.iter().map(|e| NameMatch::parse(e)).collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    /* `exe` is only read when an entry needs it */
+    pub fn matches(&self, pid: i32, comm: &str) -> bool {
+        let cell: OnceCell<Option<PathBuf>> = OnceCell::new();
+        let exe = || cell.get_or_init(|| read_exe(pid)).as_deref();
+        self.entries.iter().any(|m| match m {
+            NameMatch::Comm(name) => comm == name,
+            NameMatch::Glob(pat) => pat.matches(comm),
+            NameMatch::Regex(re) => re.is_match(comm),
+            NameMatch::Exe(name) => exe().and_then(Path::file_name).map(|f| f == name.as_str()).unwrap_or(false),
+            NameMatch::Path(path) => exe() == Some(path.as_path()),
+            NameMatch::PathGlob(pat) => exe().map(|p| pat.matches_path(p)).unwrap_or(false),
+        })
+    }
+}
+
+/* Entries that load fine but can never match a process. `running` is
+ * (pid, comm) of the processes up now, to catch plain names that used to
+ * match as substrings and now cover nothing. */
+pub fn warnings(entries: &[String], running: &[(i32, String)]) -> Vec<String> {
+    let mut out = Vec::new();
+    let exclusions = Exclusions::new(entries).ok();
+    for entry in entries {
+        let (mode, value) = split(entry);
+        match mode {
+            "comm" if value.chars().count() > COMM_LEN => out.push(format!(
+                "exclude_names entry {:?}: comm is cut to {} characters, so this never matches; use \"exe:{}\"",
+                entry, COMM_LEN, value
+            )),
+            // Kernel threads do have '/' in comm (kworker/0:1), basenames never
+            "exe" if value.contains('/') => out.push(format!(
+                "exclude_names entry {:?}: a basename has no '/'; use \"path:\" for a full path",
+                entry
+            )),
+            "path" if value.starts_with('/') && !Path::new(value).exists() => {
+                out.push(format!("exclude_names entry {:?}: {} does not exist", entry, value))
+            }
+            "glob" if !value.starts_with('/') && value.contains('/') => out.push(format!(
+                "exclude_names entry {:?}: matches comm, not the executable path; start the glob with '/' for the path",
+                entry
+            )),
+            _ => {}
+        }
+        if let (Some(ex), ("comm", value)) = (&exclusions, split(entry)) {
+            if value == entry && !running.iter().any(|(_, comm)| comm == value) {
+                let mut missed: Vec<&str> = running.iter()
+                    .filter(|(pid, comm)| comm.contains(value) && !ex.matches(*pid, comm))
+                    .map(|(_, comm)| comm.as_str())
+                    .collect();
+                missed.sort_unstable();
+                missed.dedup();
+                if !missed.is_empty() {
+                    out.push(format!(
+                        "exclude_names entry {:?}: no running process is called that exactly, and names are no longer matched as substrings; {} not excluded (list them or use \"glob:*{}*\")",
+                        entry, missed.join(", "), value
+                    ));
+                }
+            }
+        }
+        if let Some((prefix, _)) = entry.split_once(':') {
+            if !PREFIXES.contains(&prefix) && !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_alphabetic()) {
+                out.push(format!(
+                    "exclude_names entry {:?}: {:?} is not a match mode ({}); matching the whole entry as a name",
+                    entry, prefix, PREFIXES.join(", ")
+                ));
+            }
+        }
+    }
+    out
+}
+
+/* (pid, comm) of every process, for `warnings` */
+pub fn running() -> Vec<(i32, String)> {
+    let Ok(dir) = fs::read_dir("/proc") else { return Vec::new() };
+    dir.flatten()
+        .filter_map(|e| e.file_name().to_str()?.parse::<i32>().ok())
+        .filter_map(|pid| {
+            let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+            Some((pid, comm.trim_end().to_string()))
+        })
+        .collect()
+}
+
+/* Deleted or replaced binaries read as "/path (deleted)" */
+fn read_exe(pid: i32) -> Option<PathBuf> {
+    let exe = fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    match exe.to_str().and_then(|s| s.strip_suffix(" (deleted)")) {
+        Some(stripped) => Some(PathBuf::from(stripped)),
+        None => Some(exe),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(entries: &[&str]) -> Exclusions {
+        Exclusions::new(&entries.iter().map(|e| e.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_comm_is_exact() {
+        let ex = set(&["sshd", "systemd"]);
+        assert!(ex.matches(1, "systemd"));
+        assert!(!ex.matches(1, "systemd-journal"));
+        assert!(!ex.matches(1, "sshd-session"));
+
+        let ex = set(&["glob:systemd-*", "regex:^kworker/"]);
+        assert!(ex.matches(1, "systemd-journal"));
+        assert!(!ex.matches(1, "systemd"));
+        assert!(ex.matches(1, "kworker/0:1"));
+        // A colon without a known mode is part of the name
+        assert!(set(&["kworker/0:1"]).matches(1, "kworker/0:1"));
+    }
+
+    #[test]
+    fn test_exe_matching() {
+        let me = std::process::id() as i32;
+        let exe = std::env::current_exe().unwrap();
+        let base = exe.file_name().unwrap().to_str().unwrap();
+        assert!(set(&[&format!("exe:{}", base)]).matches(me, "x"));
+        assert!(set(&[&format!("path:{}", exe.display())]).matches(me, "x"));
+        assert!(set(&[&format!("glob:{}/*", exe.parent().unwrap().display())]).matches(me, "x"));
+        assert!(!set(&["path:/nonexistent/bin"]).matches(me, "x"));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(Exclusions::new(&["path:relative/bin".into()]).is_err());
+        assert!(Exclusions::new(&["regex:(".into()]).is_err());
+        assert!(Exclusions::new(&["exe:".into()]).is_err());
+
+        let entries: Vec<String> = ["gnome-shell-calendar-server", "exe:gnome-shell-calendar-server",
+                                    "glob:bin/*", "rgex:foo", "path:/nonexistent/bin", "sshd"]
+            .iter().map(|e| e.to_string()).collect();
+        let warnings = warnings(&entries, &[]);
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert!(warnings[0].contains("exe:gnome-shell-calendar-server"));
+
+        // Kernel thread names have a '/', basenames don't
+        let entries: Vec<String> = ["kworker/0:1", "comm:kworker/0:1", "exe:bin/sshd"].iter().map(|e| e.to_string()).collect();
+        let warnings = super::warnings(&entries, &[]);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("exe:bin/sshd"));
+    }
+
+    #[test]
+    fn test_substring_entries_warn() {
+        let running: Vec<(i32, String)> = [(1, "systemd"), (2, "mysqld"), (3, "dockerd"), (4, "systemd-journal")]
+            .iter().map(|(p, c)| (*p, c.to_string())).collect();
+        let entries = |e: &[&str]| e.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+        let w = warnings(&entries(&["mysql", "docker", "systemd"]), &running);
+        assert_eq!(w.len(), 2, "{:?}", w);
+        assert!(w[0].contains("mysqld not excluded"));
+        assert!(w[1].contains("dockerd not excluded"));
+        // Covered by another entry, or an exact match is running
+        assert!(warnings(&entries(&["mysql", "mysqld", "systemd", "glob:systemd-*"]), &running).is_empty());
+        assert!(warnings(&entries(&["comm:mysql"]), &running).is_empty());
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use procfs::process::Process;
+use std::time::Instant;
+
+use crate::actions;
+use crate::cgroups::CgroupInfo;
+
+/* Bookkeeping for processes paused by slow/hybrid mode, either one PID with
+ * SIGSTOP or the victim's whole cgroup through cgroup.freeze. Each entry
+ * remembers the process start time so a recycled PID is never resumed or
+ * killed by mistake. */
+
+#[derive(Debug, Clone)]
+pub struct FrozenProc {
+    pub pid: i32,
+    pub name: String,
+    pub rss_bytes: u64,
+    pub start_time: u64,
+    pub frozen_at: Instant,
+    /* Set when the whole cgroup was frozen rather than just `pid` */
+    pub cgroup: Option<CgroupInfo>,
+}
+
+impl FrozenProc {
+    /* True while the PID still refers to the process we stopped */
+    pub fn is_alive(&self) -> bool {
+        start_time(self.pid).map(|t| t == self.start_time).unwrap_or(false)
+    }
+
+    /* A frozen cgroup needs thawing as long as it exists, even if the
+     * victim itself has exited */
+    fn needs_thaw(&self) -> bool {
+        match self.cgroup {
+            Some(ref cg) => cg.fs_path().exists(),
+            None => self.is_alive(),
+        }
+    }
+
+    fn thaw(&self) -> Result<()> {
+        match self.cgroup {
+            Some(ref cg) => actions::cgroup_thaw(&cg.fs_path()),
+            None => actions::sigcont(self.pid),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FrozenSet {
+    frozen: Vec<FrozenProc>,
+}
+
+impl FrozenSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn freeze(&mut self, pid: i32, name: &str, rss_bytes: u64) -> Result<()> {
+        let start_time = start_time(pid)?;
+        actions::sigstop(pid)?;
+        self.frozen.push(FrozenProc {
+            pid,
+            name: name.to_string(),
+            rss_bytes,
+            start_time,
+            frozen_at: Instant::now(),
+            cgroup: None,
+        });
+        Ok(())
+    }
+
+    /* Freeze every task in `cgroup`. Callers check
+     * `CgroupInfo::whole_cgroup_veto` first. */
+    pub fn freeze_cgroup(&mut self, pid: i32, name: &str, rss_bytes: u64, cgroup: &CgroupInfo) -> Result<()> {
+        let start_time = start_time(pid)?;
+        actions::cgroup_freeze(&cgroup.fs_path())?;
+        self.frozen.push(FrozenProc {
+            pid,
+            name: name.to_string(),
+            rss_bytes,
+            start_time,
+            frozen_at: Instant::now(),
+            cgroup: Some(cgroup.clone()),
+        });
+        Ok(())
+    }
+
+    pub fn contains(&self, pid: i32) -> bool {
+        self.frozen.iter().any(|f| f.pid == pid)
+    }
+
+    /* True if `pid` is stopped by us, directly or through its cgroup */
+    pub fn covers(&self, pid: i32, cgroup_path: &str) -> bool {
+        self.frozen.iter().any(|f| {
+            f.pid == pid || f.cgroup.as_ref().map(|cg| cg.raw_path == cgroup_path).unwrap_or(false)
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frozen.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frozen.len()
+    }
+
+    pub fn pids(&self) -> Vec<i32> {
+        self.frozen.iter().filter(|f| f.cgroup.is_none()).map(|f| f.pid).collect()
+    }
+
+    pub fn cgroups(&self) -> Vec<String> {
+        self.frozen.iter().filter_map(|f| f.cgroup.as_ref().map(|cg| cg.raw_path.clone())).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FrozenProc> {
+        self.frozen.iter()
+    }
+
+    /* Drop entries whose process exited (or was killed by someone else) */
+    pub fn prune(&mut self) -> Vec<FrozenProc> {
+        let (alive, gone): (Vec<_>, Vec<_>) = self.frozen.drain(..).partition(|f| f.needs_thaw());
+        self.frozen = alive;
+        gone
+    }
+
+    /* Resume the most recently frozen entry. The first one we froze was the
+     * worst offender, so it stays stopped the longest. */
+    pub fn thaw_next(&mut self) -> Option<(FrozenProc, Result<()>)> {
+        while let Some(f) = self.frozen.pop() {
+            if f.needs_thaw() {
+                let res = f.thaw();
+                return Some((f, res));
+            }
+        }
+        None
+    }
+
+    pub fn thaw_all(&mut self) -> Vec<(FrozenProc, Result<()>)> {
+        let mut out = Vec::new();
+        while let Some(thawed) = self.thaw_next() {
+            out.push(thawed);
+        }
+        out
+    }
+
+    /* Oldest frozen process that is still alive. The ladder decides when
+     * freezing has had its time; hybrid mode kills this one next. */
+    pub fn escalation_candidate(&self) -> Option<&FrozenProc> {
+        self.frozen.iter().find(|f| f.is_alive())
+    }
+
+    /* Stop tracking `pid` after it was killed. A frozen cgroup is thawed so
+     * the rest of its tasks don't stay stopped forever. */
+    pub fn release(&mut self, pid: i32) -> Option<(FrozenProc, Result<()>)> {
+        let idx = self.frozen.iter().position(|f| f.pid == pid)?;
+        let f = self.frozen.remove(idx);
+        let res = if f.cgroup.is_some() && f.needs_thaw() { f.thaw() } else { Ok(()) };
+        Some((f, res))
+    }
+}
+
+fn start_time(pid: i32) -> Result<u64> {
+    let stat = Process::new(pid)
+        .and_then(|p| p.stat())
+        .with_context(|| format!("reading /proc/{}/stat", pid))?;
+    Ok(stat.starttime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Child, Command, Stdio};
+    use std::time::Duration;
+
+    /* Kills the child on drop so a failed assertion never leaves it stopped */
+    struct Sleeper(Child);
+
+    impl Sleeper {
+        fn spawn() -> Self {
+            let child = Command::new("sleep")
+                .arg("30")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+            Sleeper(child)
+        }
+
+        fn pid(&self) -> i32 {
+            self.0.id() as i32
+        }
+    }
+
+    impl Drop for Sleeper {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    /* Signal delivery is asynchronous; give the kernel a moment */
+    fn wait_stopped(pid: i32, stopped: bool) -> bool {
+        for _ in 0..100 {
+            let state = Process::new(pid).unwrap().stat().unwrap().state;
+            if (state == 'T') == stopped {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_freeze_and_thaw_order() {
+        let a = Sleeper::spawn();
+        let b = Sleeper::spawn();
+        let (pa, pb) = (a.pid(), b.pid());
+
+        let mut set = FrozenSet::new();
+        set.freeze(pa, "sleep", 0).unwrap();
+        set.freeze(pb, "sleep", 0).unwrap();
+        assert_eq!(set.pids(), vec![pa, pb]);
+        assert!(wait_stopped(pa, true));
+
+        // Last frozen is resumed first
+        let (thawed, res) = set.thaw_next().unwrap();
+        assert!(res.is_ok());
+        assert_eq!(thawed.pid, pb);
+        assert!(set.contains(pa));
+        assert!(!set.contains(pb));
+
+        set.thaw_all();
+        assert!(set.is_empty());
+        assert!(wait_stopped(pa, false));
+    }
+
+    #[test]
+    fn test_prune_and_escalation() {
+        let mut child = Sleeper::spawn();
+        let pid = child.pid();
+
+        let mut set = FrozenSet::new();
+        set.freeze(pid, "sleep", 0).unwrap();
+        assert_eq!(set.escalation_candidate().map(|f| f.pid), Some(pid));
+
+        let _ = child.0.kill();
+        let _ = child.0.wait();
+
+        let gone = set.prune();
+        assert_eq!(gone.len(), 1);
+        assert!(set.is_empty());
+        assert!(set.thaw_next().is_none());
+    }
+}
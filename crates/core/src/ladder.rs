@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::cgroups::{self, Hierarchy};
+use crate::config::Config;
+use crate::policy::PressureState;
+
+/* Escalation ladder: an ordered list of actions the daemon works through
+ * while pressure lasts. Pressure starts at the first rung its level allows;
+ * each rung is given `timeout_sec` to help before the next one is entered.
+ * Rungs meant for milder pressure than the current state are passed straight
+ * through. When the next rung can't be entered (it needs hard pressure, or
+ * this is the top) the current one is repeated once its timeout is up.
+ * Back at healthy the walk starts over. */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LadderAction {
+    /* Give back reserve chunks in proportion to pressure depth */
+    ReleaseReserve,
+    /* memory.reclaim from low-priority cgroups; soft pressure only */
+    Reclaim,
+    /* Lower memory.high on the worst cgroup */
+    Throttle,
+    /* SIGSTOP / cgroup.freeze the top candidate */
+    Freeze,
+    /* SIGTERM, preferring a process we already froze */
+    Term,
+    /* Kill at `kill_granularity`, preferring a process we already froze */
+    Kill,
+}
+
+impl LadderAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LadderAction::ReleaseReserve => "release-reserve",
+            LadderAction::Reclaim => "reclaim",
+            LadderAction::Throttle => "throttle",
+            LadderAction::Freeze => "freeze",
+            LadderAction::Term => "term",
+            LadderAction::Kill => "kill",
+        }
+    }
+
+    /* Everything except the reserve touches other processes */
+    pub fn acts_on_processes(&self) -> bool {
+        *self != LadderAction::ReleaseReserve
+    }
+}
+
+impl fmt::Display for LadderAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rung {
+    pub action: LadderAction,
+    /* Least pressure at which the rung may be entered: "soft" or "hard" */
+    #[serde(default = "default_when")]
+    pub when: PressureState,
+    /* How long the rung gets before escalating (or repeating) */
+    #[serde(default)]
+    pub timeout_sec: u64,
+}
+
+fn default_when() -> PressureState { PressureState::Hard }
+
+impl Rung {
+    pub fn new(action: LadderAction, when: PressureState, timeout_sec: u64) -> Self {
+        Self { action, when, timeout_sec }
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_sec)
+    }
+}
+
+/* The ladder in effect for `cfg`: `[[ladder]]` if configured, otherwise
+ * one built from `mode` and the older per-mode settings */
+pub fn rungs_for(cfg: &Config) -> Vec<Rung> {
+    rungs_for_hierarchy(cfg, cgroups::hierarchy())
+}
+
+/* Reclaim and throttle need the v2 memory controller; without it the
+ * default ladder leaves them out rather than waiting on rungs that can't
+ * act. A configured ladder is taken as is. */
+pub fn rungs_for_hierarchy(cfg: &Config, hierarchy: Hierarchy) -> Vec<Rung> {
+    use LadderAction::*;
+    use PressureState::{Hard, Soft};
+
+    if !cfg.ladder.is_empty() {
+        return cfg.ladder.clone();
+    }
+    let mut rungs = vec![Rung::new(ReleaseReserve, Soft, 0)];
+    if cfg.mode == "watch" {
+        return rungs;
+    }
+    let memcg = hierarchy == Hierarchy::Unified;
+    if cfg.reclaim_enabled && memcg {
+        // Until the dwell time is up: if reclaim got avail% out of soft
+        // pressure, the state drops back before throttling starts
+        rungs.push(Rung::new(Reclaim, Soft, cfg.dwell_soft_sec));
+    }
+    if cfg.soft_throttle_enabled && memcg {
+        rungs.push(Rung::new(Throttle, Soft, cfg.scan_interval_sec));
+    }
+    // Kill rungs get the same minimum timeout configured ones are held to,
+    // so a victim still exiting isn't signalled again on the next tick
+    let kill = Rung::new(Kill, Hard, cfg.scan_interval_sec.max(1));
+    match cfg.mode.as_str() {
+        "slow" => rungs.push(Rung::new(Freeze, Hard, 0)),
+        "hybrid" => {
+            rungs.push(Rung::new(Freeze, Hard, cfg.freeze_escalate_sec));
+            rungs.push(kill);
+        }
+        "kill" => rungs.push(kill),
+        _ => {}
+    }
+    rungs
+}
+
+#[derive(Debug, Clone)]
+pub struct Ladder {
+    rungs: Vec<Rung>,
+    /* Current rung and when it was entered (or last repeated) */
+    pos: Option<(usize, Instant)>,
+}
+
+impl Ladder {
+    pub fn new(rungs: Vec<Rung>) -> Self {
+        Self { rungs, pos: None }
+    }
+
+    /* Swap in a new ladder; the walk starts over if it changed */
+    pub fn set_rungs(&mut self, rungs: Vec<Rung>) {
+        if rungs != self.rungs {
+            self.rungs = rungs;
+            self.pos = None;
+        }
+    }
+
+    pub fn rungs(&self) -> &[Rung] {
+        &self.rungs
+    }
+
+    pub fn position(&self) -> Option<usize> {
+        self.pos.map(|(i, _)| i)
+    }
+
+    pub fn current(&self) -> Option<&Rung> {
+        self.position().map(|i| &self.rungs[i])
+    }
+
+    /* How long the current rung has been in effect */
+    pub fn held(&self) -> Option<Duration> {
+        self.pos.map(|(_, since)| since.elapsed())
+    }
+
+    /* True once a rung with `action` has been entered during this episode */
+    pub fn reached(&self, action: LadderAction) -> bool {
+        match self.position() {
+            Some(i) => self.rungs[..=i].iter().any(|r| r.action == action),
+            None => false,
+        }
+    }
+
+    pub fn step(&mut self, state: PressureState) -> Vec<usize> {
+        self.step_at(Instant::now(), state)
+    }
+
+    /* Advance for one tick at `state`; returns the rungs whose action runs
+     * now, lowest first */
+    pub fn step_at(&mut self, now: Instant, state: PressureState) -> Vec<usize> {
+        if state == PressureState::Healthy {
+            self.pos = None;
+            return Vec::new();
+        }
+
+        let mut out = Vec::new();
+        let (mut i, mut since) = match self.pos {
+            Some(pos) => pos,
+            None => match self.rungs.iter().position(|r| r.when <= state) {
+                Some(first) => {
+                    out.push(first);
+                    (first, now)
+                }
+                None => return out,
+            },
+        };
+
+        loop {
+            let rung = &self.rungs[i];
+            let timed_out = now.saturating_duration_since(since) >= rung.timeout();
+            if !timed_out && rung.when >= state {
+                break;
+            }
+            match self.rungs.get(i + 1) {
+                Some(next) if next.when <= state => {
+                    i += 1;
+                    since = now;
+                    out.push(i);
+                }
+                _ => {
+                    if timed_out && rung.when <= state && !out.contains(&i) {
+                        since = now;
+                        out.push(i);
+                    }
+                    break;
+                }
+            }
+        }
+        self.pos = Some((i, since));
+        out
+    }
+
+    /* What walking the ladder from scratch under constant `state` would do,
+     * stepping every `tick`: (time since pressure began, rung). Stops after
+     * the first repeat or at `limit`. */
+    pub fn plan(&self, state: PressureState, tick: Duration, limit: Duration) -> Vec<(Duration, usize)> {
+        let mut ladder = Ladder::new(self.rungs.clone());
+        let t0 = Instant::now();
+        let tick = tick.max(Duration::from_secs(1));
+        let mut out: Vec<(Duration, usize)> = Vec::new();
+        let mut t = Duration::ZERO;
+        while t <= limit {
+            for i in ladder.step_at(t0 + t, state) {
+                let repeat = out.iter().any(|&(_, j)| j == i);
+                out.push((t, i));
+                if repeat {
+                    return out;
+                }
+            }
+            t += tick;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LadderAction::*;
+    use PressureState::*;
+
+    fn ladder() -> Ladder {
+        Ladder::new(vec![
+            Rung::new(ReleaseReserve, Soft, 0),
+            Rung::new(Throttle, Soft, 10),
+            Rung::new(Freeze, Hard, 10),
+            Rung::new(Kill, Hard, 2),
+        ])
+    }
+
+    #[test]
+    fn test_soft_pressure_stays_on_soft_rungs() {
+        let mut l = ladder();
+        let t0 = Instant::now();
+        // Release reserve has no timeout, so throttling starts the same tick
+        assert_eq!(l.step_at(t0, Soft), vec![0, 1]);
+        assert!(l.reached(ReleaseReserve));
+        assert!(l.step_at(t0 + Duration::from_secs(5), Soft).is_empty());
+        // Freezing needs hard pressure: throttle another cgroup instead
+        assert_eq!(l.step_at(t0 + Duration::from_secs(10), Soft), vec![1]);
+        assert_eq!(l.position(), Some(1));
+
+        l.step_at(t0 + Duration::from_secs(11), Healthy);
+        assert_eq!(l.position(), None);
+    }
+
+    #[test]
+    fn test_hard_pressure_escalates_after_timeout() {
+        let mut l = ladder();
+        let t0 = Instant::now();
+        // Soft rungs are passed through without their timeout
+        assert_eq!(l.step_at(t0, Hard), vec![0, 1, 2]);
+        assert!(l.step_at(t0 + Duration::from_secs(5), Hard).is_empty());
+        assert_eq!(l.step_at(t0 + Duration::from_secs(10), Hard), vec![3]);
+        // The top rung repeats
+        assert_eq!(l.step_at(t0 + Duration::from_secs(12), Hard), vec![3]);
+        // Easing to soft holds position without acting
+        assert!(l.step_at(t0 + Duration::from_secs(14), Soft).is_empty());
+        assert_eq!(l.position(), Some(3));
+    }
+
+    #[test]
+    fn test_plan() {
+        let plan = ladder().plan(Hard, Duration::from_secs(2), Duration::from_secs(60));
+        let steps: Vec<(u64, usize)> = plan.iter().map(|(t, i)| (t.as_secs(), *i)).collect();
+        assert_eq!(steps, vec![(0, 0), (0, 1), (0, 2), (10, 3), (12, 3)]);
+
+        let mut cfg = Config { mode: "watch".into(), ..Config::default() };
+        assert_eq!(rungs_for(&cfg).len(), 1);
+        cfg.mode = "hybrid".into();
+        let actions: Vec<LadderAction> = rungs_for_hierarchy(&cfg, Hierarchy::Unified).iter().map(|r| r.action).collect();
+        assert_eq!(actions, vec![ReleaseReserve, Reclaim, Throttle, Freeze, Kill]);
+        // The default kill rung passes the same check configured ones do
+        let configured = Config { ladder: rungs_for_hierarchy(&cfg, Hierarchy::Unified), ..Config::default() };
+        assert!(configured.validate().is_ok());
+        cfg.scan_interval_sec = 0;
+        assert!(rungs_for_hierarchy(&cfg, Hierarchy::Unified).iter().all(|r| r.action != Kill || r.timeout_sec > 0));
+        // No memory.reclaim or memory.high on the v1 memory controller
+        let actions: Vec<LadderAction> = rungs_for_hierarchy(&cfg, Hierarchy::Hybrid).iter().map(|r| r.action).collect();
+        assert_eq!(actions, vec![ReleaseReserve, Freeze, Kill]);
+    }
+}
@@ -1,11 +1,23 @@
 pub mod config;
 pub mod mem;
+pub mod exclude;
 pub mod procinfo;
+pub mod proctree;
 pub mod reserve;
 pub mod policy;
+pub mod rules;
+pub mod ladder;
 pub mod actions;
+pub mod freeze;
+pub mod ratelimit;
+pub mod throttle;
+pub mod reclaim;
 pub mod psi;
+pub mod trend;
 pub mod cgroups;
+pub mod control;
+pub mod zram;
+pub mod util;
 
 #[cfg(test)]
 mod config_test;
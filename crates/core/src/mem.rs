@@ -1,33 +1,253 @@
-use anyhow::Result;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
 
-#[derive(Debug, Clone)]
-pub struct MemSample {
+/* Parsed /proc/meminfo. Sizes are in kB as the kernel reports them,
+ * HugePages_* are page counts. Fields missing on older kernels read as 0,
+ * except where absence changes meaning (MemAvailable, zswap). */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemInfo {
     pub mem_total: u64,
-    pub mem_available: u64,
-    pub avail_pct: f64,
-    pub total_kb: u64,
+    pub mem_free: u64,
+    /* Kernel 3.14+ */
+    pub mem_available: Option<u64>,
+    pub buffers: u64,
+    pub cached: u64,
+    pub swap_cached: u64,
+    pub active: u64,
+    pub inactive: u64,
+    pub active_anon: u64,
+    pub inactive_anon: u64,
+    pub active_file: u64,
+    pub inactive_file: u64,
+    pub unevictable: u64,
+    pub mlocked: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+    /* Kernel 5.19+, only with zswap built in */
+    pub zswap: Option<u64>,
+    pub zswapped: Option<u64>,
+    pub dirty: u64,
+    pub writeback: u64,
+    pub anon_pages: u64,
+    pub mapped: u64,
+    pub shmem: u64,
+    pub kreclaimable: u64,
+    pub slab: u64,
+    pub sreclaimable: u64,
+    pub sunreclaim: u64,
+    pub kernel_stack: u64,
+    pub page_tables: u64,
+    pub sec_page_tables: u64,
+    pub commit_limit: u64,
+    pub committed_as: u64,
+    pub vmalloc_total: u64,
+    pub vmalloc_used: u64,
+    pub percpu: u64,
+    pub hardware_corrupted: u64,
+    pub anon_huge_pages: u64,
+    pub shmem_huge_pages: u64,
+    pub file_huge_pages: u64,
+    pub hugepages_total: u64,
+    pub hugepages_free: u64,
+    pub hugepages_rsvd: u64,
+    pub hugepages_surp: u64,
+    pub hugepagesize: u64,
+    pub hugetlb: u64,
 }
 
-pub fn sample() -> Result<MemSample> {
-    let file = File::open("/proc/meminfo")?;
-    let reader = BufReader::new(file);
-    let mut mem_total = 0u64;
-    let mut mem_available = 0u64;
-    let mut mem_free = 0u64;
-    for line in reader.lines() {
-        let l = line?;
-        if l.starts_with("MemTotal:") {
-            mem_total = l.split_whitespace().nth(1).and_then(|v| v.parse().ok()).unwrap_or(0);
-        } else if l.starts_with("MemAvailable:") {
-            mem_available = l.split_whitespace().nth(1).and_then(|v| v.parse().ok()).unwrap_or(0);
-        } else if l.starts_with("MemFree:") {
-            mem_free = l.split_whitespace().nth(1).and_then(|v| v.parse().ok()).unwrap_or(0);
+/* Where the memory went, in kB. The buckets don't overlap, so together with
+ * `other` they add up to MemTotal. */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemBreakdown {
+    pub anon: u64,
+    /* Page cache without shmem */
+    pub file_cache: u64,
+    /* shmem, tmpfs and shared anonymous mappings */
+    pub shmem: u64,
+    pub slab_reclaimable: u64,
+    pub slab_unreclaimable: u64,
+    /* Kernel stacks, page tables, per-cpu allocations */
+    pub kernel: u64,
+    /* Reserved for hugetlbfs, used or not */
+    pub hugetlb: u64,
+    pub free: u64,
+    /* Everything the kernel doesn't itemise (vmalloc, drivers, ...) */
+    pub other: u64,
+}
+
+impl MemInfo {
+    /* MemAvailable, or MemFree on kernels that predate it */
+    pub fn available(&self) -> u64 {
+        self.mem_available.unwrap_or(self.mem_free)
+    }
+
+    pub fn avail_pct(&self) -> f64 {
+        if self.mem_total == 0 {
+            return 0.0;
+        }
+        (self.available() as f64 / self.mem_total as f64) * 100.0
+    }
+
+    pub fn used(&self) -> u64 {
+        self.mem_total.saturating_sub(self.available())
+    }
+
+    /* Percent of swap still free, or None on a box without swap */
+    pub fn swap_free_pct(&self) -> Option<f64> {
+        if self.swap_total == 0 {
+            return None;
         }
+        Some((self.swap_free as f64 / self.swap_total as f64) * 100.0)
+    }
+
+    pub fn breakdown(&self) -> MemBreakdown {
+        let hugetlb = if self.hugetlb > 0 { self.hugetlb } else { self.hugepages_total * self.hugepagesize };
+        let mut b = MemBreakdown {
+            anon: self.anon_pages,
+            // Cached counts shmem pages too
+            file_cache: (self.cached + self.buffers).saturating_sub(self.shmem),
+            shmem: self.shmem,
+            slab_reclaimable: self.sreclaimable,
+            slab_unreclaimable: self.sunreclaim,
+            kernel: self.kernel_stack + self.page_tables + self.sec_page_tables + self.percpu,
+            hugetlb,
+            free: self.mem_free,
+            other: 0,
+        };
+        let accounted = b.anon + b.file_cache + b.shmem + b.slab_reclaimable + b.slab_unreclaimable
+            + b.kernel + b.hugetlb + b.free;
+        b.other = self.mem_total.saturating_sub(accounted);
+        b
+    }
+}
+
+pub fn sample() -> Result<MemInfo> {
+    let content = fs::read_to_string("/proc/meminfo")?;
+    parse(&content)
+}
+
+/* Unknown keys and malformed lines are skipped; only MemTotal is required */
+pub fn parse(content: &str) -> Result<MemInfo> {
+    let mut m = MemInfo::default();
+    for line in content.lines() {
+        let Some((key, rest)) = line.split_once(':') else { continue };
+        let Some(value) = rest.split_whitespace().next().and_then(|v| v.parse::<u64>().ok()) else { continue };
+        match key.trim() {
+            "MemTotal" => m.mem_total = value,
+            "MemFree" => m.mem_free = value,
+            "MemAvailable" => m.mem_available = Some(value),
+            "Buffers" => m.buffers = value,
+            "Cached" => m.cached = value,
+            "SwapCached" => m.swap_cached = value,
+            "Active" => m.active = value,
+            "Inactive" => m.inactive = value,
+            "Active(anon)" => m.active_anon = value,
+            "Inactive(anon)" => m.inactive_anon = value,
+            "Active(file)" => m.active_file = value,
+            "Inactive(file)" => m.inactive_file = value,
+            "Unevictable" => m.unevictable = value,
+            "Mlocked" => m.mlocked = value,
+            "SwapTotal" => m.swap_total = value,
+            "SwapFree" => m.swap_free = value,
+            "Zswap" => m.zswap = Some(value),
+            "Zswapped" => m.zswapped = Some(value),
+            "Dirty" => m.dirty = value,
+            "Writeback" => m.writeback = value,
+            "AnonPages" => m.anon_pages = value,
+            "Mapped" => m.mapped = value,
+            "Shmem" => m.shmem = value,
+            "KReclaimable" => m.kreclaimable = value,
+            "Slab" => m.slab = value,
+            "SReclaimable" => m.sreclaimable = value,
+            "SUnreclaim" => m.sunreclaim = value,
+            "KernelStack" => m.kernel_stack = value,
+            "PageTables" => m.page_tables = value,
+            "SecPageTables" => m.sec_page_tables = value,
+            "CommitLimit" => m.commit_limit = value,
+            "Committed_AS" => m.committed_as = value,
+            "VmallocTotal" => m.vmalloc_total = value,
+            "VmallocUsed" => m.vmalloc_used = value,
+            "Percpu" => m.percpu = value,
+            "HardwareCorrupted" => m.hardware_corrupted = value,
+            "AnonHugePages" => m.anon_huge_pages = value,
+            "ShmemHugePages" => m.shmem_huge_pages = value,
+            "FileHugePages" => m.file_huge_pages = value,
+            "HugePages_Total" => m.hugepages_total = value,
+            "HugePages_Free" => m.hugepages_free = value,
+            "HugePages_Rsvd" => m.hugepages_rsvd = value,
+            "HugePages_Surp" => m.hugepages_surp = value,
+            "Hugepagesize" => m.hugepagesize = value,
+            "Hugetlb" => m.hugetlb = value,
+            _ => {}
+        }
+    }
+    if m.mem_total == 0 {
+        bail!("/proc/meminfo has no MemTotal");
+    }
+    Ok(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "MemTotal:       16000000 kB
+MemFree:         1000000 kB
+MemAvailable:    4000000 kB
+Buffers:          200000 kB
+Cached:          3000000 kB
+SwapCached:        10000 kB
+Active(anon):    6000000 kB
+SwapTotal:       8000000 kB
+SwapFree:         160000 kB
+AnonPages:       9000000 kB
+Mapped:           500000 kB
+Shmem:            800000 kB
+SReclaimable:     400000 kB
+SUnreclaim:       300000 kB
+KernelStack:       20000 kB
+PageTables:        80000 kB
+Percpu:             4000 kB
+CommitLimit:    16000000 kB
+Committed_AS:   22000000 kB
+DirectMap2M:    bogus
+HugePages_Total:     512
+HugePages_Free:      512
+Hugepagesize:       2048 kB
+Hugetlb:         1048576 kB
+";
+
+    #[test]
+    fn test_parse_meminfo() {
+        let m = parse(SAMPLE).unwrap();
+        assert_eq!(m.mem_total, 16000000);
+        assert_eq!(m.avail_pct(), 25.0);
+        assert_eq!(m.swap_free_pct(), Some(2.0));
+        assert_eq!(m.active_anon, 6000000);
+        assert_eq!(m.committed_as, 22000000);
+        assert_eq!(m.hugepages_total, 512);
+        assert_eq!(m.zswap, None);
+
+        let old = parse("MemTotal: 1000 kB\nMemFree: 500 kB\nSwapTotal: 0 kB\nSwapFree: 0 kB\n").unwrap();
+        assert_eq!(old.mem_available, None);
+        assert_eq!(old.avail_pct(), 50.0);
+        assert_eq!(old.swap_free_pct(), None);
+
+        assert!(parse("garbage\n").is_err());
+    }
+
+    #[test]
+    fn test_breakdown_adds_up() {
+        let m = parse(SAMPLE).unwrap();
+        let b = m.breakdown();
+        assert_eq!(b.anon, 9000000);
+        assert_eq!(b.file_cache, 2400000);
+        assert_eq!(b.shmem, 800000);
+        assert_eq!(b.kernel, 104000);
+        assert_eq!(b.hugetlb, 1048576);
+        let sum = b.anon + b.file_cache + b.shmem + b.slab_reclaimable + b.slab_unreclaimable
+            + b.kernel + b.hugetlb + b.free + b.other;
+        assert_eq!(sum, m.mem_total);
     }
-    // MemAvailable was added in kernel 3.14 - fall back to MemFree if missing
-    let avail = if mem_available > 0 { mem_available } else { mem_free };
-    let pct = if mem_total > 0 { (avail as f64 / mem_total as f64) * 100.0 } else { 0.0 };
-    Ok(MemSample { mem_total, mem_available: avail, avail_pct: pct, total_kb: mem_total })
 }
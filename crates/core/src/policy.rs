@@ -1,9 +1,15 @@
 use crate::psi::PSIMetrics;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/* Ordered by severity */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum PressureState {
+    #[serde(alias = "healthy")]
     Healthy,
+    #[serde(alias = "soft")]
     Soft,
+    #[serde(alias = "hard")]
     Hard,
 }
 
@@ -17,8 +23,31 @@ pub fn classify(avail_pct: f64, soft: u8, hard: u8) -> PressureState {
     }
 }
 
-/* Dual-threshold model: PSI OR meminfo (whichever triggers first).
- * Allows early intervention on memory pressure before available% drops. */
+/* Swap fill level against its thresholds, both in percent of SwapTotal free */
+#[derive(Debug, Clone, Copy)]
+pub struct SwapLevel {
+    pub free_pct: f64,
+    pub soft: u8,
+    pub hard: u8,
+    /* Only count exhausted swap as hard while RAM or PSI show soft pressure */
+    pub hard_needs_mem: bool,
+}
+
+impl SwapLevel {
+    pub fn state(&self) -> PressureState {
+        classify(self.free_pct, self.soft, self.hard)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.state() == PressureState::Hard
+    }
+}
+
+/* Multi-signal model: PSI OR meminfo OR swap (whichever triggers first).
+ * Allows early intervention on memory pressure before available% drops.
+ * Exhausted swap is hard pressure even while MemAvailable looks healthy,
+ * since the next burst of anonymous memory has nowhere to go; with
+ * `hard_needs_mem` it is only soft until RAM or PSI agree. */
 pub fn classify_with_psi(
     avail_pct: f64,
     soft_mem: u8,
@@ -26,21 +55,171 @@ pub fn classify_with_psi(
     psi_metrics: Option<&PSIMetrics>,
     psi_soft: f64,
     psi_hard: f64,
+    swap: Option<SwapLevel>,
 ) -> PressureState {
     let mem_state = classify(avail_pct, soft_mem, hard_mem);
+    let mut state = mem_state;
     
     if let Some(psi) = psi_metrics {
         let psi_avg10 = psi.some_avg10;
         
-        if psi_avg10 >= psi_hard || mem_state == PressureState::Hard {
-            return PressureState::Hard;
-        }
-        
-        if psi_avg10 >= psi_soft || mem_state == PressureState::Soft {
-            return PressureState::Soft;
+        if psi_avg10 >= psi_hard {
+            state = PressureState::Hard;
+        } else if psi_avg10 >= psi_soft {
+            state = state.max(PressureState::Soft);
         }
     }
+
+    if let Some(swap) = swap {
+        let swap_state = match swap.state() {
+            PressureState::Hard if swap.hard_needs_mem && state == PressureState::Healthy => PressureState::Soft,
+            s => s,
+        };
+        state = state.max(swap_state);
+    }
     
-    mem_state
+    state
 }
 
+/* Minimum time to stay in a state before stepping down from it. Stepping up
+ * is never delayed. */
+#[derive(Debug, Clone, Copy)]
+pub struct Dwell {
+    pub soft: Duration,
+    pub hard: Duration,
+}
+
+impl Dwell {
+    fn of(&self, state: PressureState) -> Duration {
+        match state {
+            PressureState::Healthy => Duration::ZERO,
+            PressureState::Soft => self.soft,
+            PressureState::Hard => self.hard,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transition {
+    pub from: PressureState,
+    pub to: PressureState,
+    pub cause: String,
+    /* How long `from` lasted */
+    pub held_secs: u64,
+}
+
+/* Pressure state with hysteresis. Each tick the caller classifies twice:
+ * `enter` with the normal thresholds and `exit` with thresholds moved by the
+ * hysteresis margin towards "healthy". The state rises as soon as `enter`
+ * does, but only falls to what `exit` allows, and only once the current
+ * state has lasted its dwell time - so a value hovering at a threshold
+ * doesn't flap. */
+#[derive(Debug)]
+pub struct PressureMachine {
+    state: PressureState,
+    since: Instant,
+    dwell: Dwell,
+}
+
+impl PressureMachine {
+    pub fn new(dwell: Dwell) -> Self {
+        Self { state: PressureState::Healthy, since: Instant::now(), dwell }
+    }
+
+    pub fn set_dwell(&mut self, dwell: Dwell) {
+        self.dwell = dwell;
+    }
+
+    pub fn state(&self) -> PressureState {
+        self.state
+    }
+
+    pub fn since(&self) -> Instant {
+        self.since
+    }
+
+    pub fn update(&mut self, enter: PressureState, exit: PressureState, cause: &str) -> Option<Transition> {
+        self.update_at(Instant::now(), enter, exit, cause)
+    }
+
+    pub fn update_at(
+        &mut self,
+        now: Instant,
+        enter: PressureState,
+        exit: PressureState,
+        cause: &str,
+    ) -> Option<Transition> {
+        let held = now.saturating_duration_since(self.since);
+        let next = if enter > self.state {
+            enter
+        } else if exit.max(enter) < self.state && held >= self.dwell.of(self.state) {
+            // One level at a time: hard always passes through soft
+            match self.state {
+                PressureState::Hard => PressureState::Soft,
+                _ => PressureState::Healthy,
+            }
+        } else {
+            return None;
+        };
+
+        let t = Transition { from: self.state, to: next, cause: cause.to_string(), held_secs: held.as_secs() };
+        self.state = next;
+        self.since = now;
+        Some(t)
+    }
+}
+
+/* Pre-emptive state from the predicted time until headroom runs out. The
+ * trend alone only reaches Soft; Hard also needs `current`, the threshold
+ * classification, to show pressure already. */
+pub fn classify_trend(
+    time_to_exhaustion: Option<Duration>,
+    soft_horizon: Duration,
+    hard_horizon: Duration,
+    current: PressureState,
+) -> PressureState {
+    match time_to_exhaustion {
+        Some(t) if t <= hard_horizon && current >= PressureState::Soft => PressureState::Hard,
+        Some(t) if t <= soft_horizon => PressureState::Soft,
+        _ => PressureState::Healthy,
+    }
+}
+
+/* How far into the soft band the system is: 0.0 at the soft threshold, 1.0
+ * at the hard one. PSI some_avg10 is scaled the same way between psi_soft
+ * and psi_hard and the deeper of the two wins. */
+pub fn pressure_depth(
+    avail_pct: f64,
+    soft_mem: u8,
+    hard_mem: u8,
+    psi_metrics: Option<&PSIMetrics>,
+    psi_soft: f64,
+    psi_hard: f64,
+) -> f64 {
+    let mem_depth = band_depth(soft_mem as f64 - avail_pct, soft_mem as f64 - hard_mem as f64);
+    let psi_depth = psi_metrics
+        .map(|psi| band_depth(psi.some_avg10 - psi_soft, psi_hard - psi_soft))
+        .unwrap_or(0.0);
+    mem_depth.max(psi_depth)
+}
+
+fn band_depth(into: f64, width: f64) -> f64 {
+    if width <= 0.0 {
+        return if into >= 0.0 { 1.0 } else { 0.0 };
+    }
+    (into / width).clamp(0.0, 1.0)
+}
+
+/* Reserve chunks to keep at this state and depth: all of them when healthy,
+ * none under hard pressure, and in between shrinking with depth - soft
+ * pressure always gives back at least one chunk. */
+pub fn reserve_target_chunks(state: PressureState, depth: f64, total: usize) -> usize {
+    match state {
+        PressureState::Healthy => total,
+        PressureState::Hard => 0,
+        PressureState::Soft => {
+            let keep = ((1.0 - depth.clamp(0.0, 1.0)) * total as f64).floor() as usize;
+            keep.min(total.saturating_sub(1))
+        }
+    }
+}
@@ -1,5 +1,10 @@
 mod tests {
-    use crate::policy::{classify, PressureState};
+    use crate::policy::{
+        classify, classify_trend, classify_with_psi, pressure_depth, reserve_target_chunks, Dwell, PressureMachine,
+        PressureState, SwapLevel,
+    };
+    use std::time::{Duration, Instant};
+    use crate::psi::PSIMetrics;
 
     #[test]
     fn test_classify() {
@@ -7,4 +12,104 @@ mod tests {
         assert_eq!(classify(10.0, 15, 5), PressureState::Soft);
         assert_eq!(classify(3.0, 15, 5), PressureState::Hard);
     }
+
+    #[test]
+    fn test_pressure_depth() {
+        assert_eq!(pressure_depth(20.0, 15, 5, None, 10.0, 30.0), 0.0);
+        assert_eq!(pressure_depth(10.0, 15, 5, None, 10.0, 30.0), 0.5);
+        assert_eq!(pressure_depth(2.0, 15, 5, None, 10.0, 30.0), 1.0);
+
+        // PSI deeper into its band than meminfo wins
+        let psi = PSIMetrics { some_avg10: 25.0, ..Default::default() };
+        assert_eq!(pressure_depth(14.0, 15, 5, Some(&psi), 10.0, 30.0), 0.75);
+    }
+
+    #[test]
+    fn test_reserve_target_chunks() {
+        assert_eq!(reserve_target_chunks(PressureState::Healthy, 0.0, 8), 8);
+        assert_eq!(reserve_target_chunks(PressureState::Hard, 0.0, 8), 0);
+        // Just inside the soft band still gives one chunk back
+        assert_eq!(reserve_target_chunks(PressureState::Soft, 0.0, 8), 7);
+        assert_eq!(reserve_target_chunks(PressureState::Soft, 0.5, 8), 4);
+        assert_eq!(reserve_target_chunks(PressureState::Soft, 1.0, 8), 0);
+    }
+
+    #[test]
+    fn test_classify_with_psi() {
+        let psi = PSIMetrics { some_avg10: 12.0, ..Default::default() };
+        assert_eq!(classify_with_psi(50.0, 15, 5, None, 10.0, 30.0, None), PressureState::Healthy);
+        assert_eq!(classify_with_psi(50.0, 15, 5, Some(&psi), 10.0, 30.0, None), PressureState::Soft);
+        assert_eq!(classify_with_psi(3.0, 15, 5, Some(&psi), 10.0, 30.0, None), PressureState::Hard);
+    }
+
+    #[test]
+    fn test_classify_swap() {
+        let swap = |free_pct| Some(SwapLevel { free_pct, soft: 20, hard: 5, hard_needs_mem: false });
+        assert_eq!(classify_with_psi(50.0, 15, 5, None, 10.0, 30.0, swap(60.0)), PressureState::Healthy);
+        assert_eq!(classify_with_psi(50.0, 15, 5, None, 10.0, 30.0, swap(10.0)), PressureState::Soft);
+        // 98% full swap is hard pressure even with MemAvailable healthy
+        assert_eq!(classify_with_psi(50.0, 15, 5, None, 10.0, 30.0, swap(2.0)), PressureState::Hard);
+        assert!(swap(2.0).unwrap().is_exhausted());
+
+        // Opted in: exhausted swap alone only raises soft pressure...
+        let gated = |free_pct| Some(SwapLevel { free_pct, soft: 20, hard: 5, hard_needs_mem: true });
+        assert_eq!(classify_with_psi(50.0, 15, 5, None, 10.0, 30.0, gated(2.0)), PressureState::Soft);
+        // ...until RAM or PSI show soft pressure too
+        assert_eq!(classify_with_psi(12.0, 15, 5, None, 10.0, 30.0, gated(2.0)), PressureState::Hard);
+        let psi = PSIMetrics { some_avg10: 15.0, ..Default::default() };
+        assert_eq!(classify_with_psi(50.0, 15, 5, Some(&psi), 10.0, 30.0, gated(2.0)), PressureState::Hard);
+    }
+
+    #[test]
+    fn test_classify_trend() {
+        use PressureState::*;
+        let (soft, hard) = (Duration::from_secs(60), Duration::from_secs(15));
+        assert_eq!(classify_trend(None, soft, hard, Soft), Healthy);
+        assert_eq!(classify_trend(Some(Duration::from_secs(300)), soft, hard, Soft), Healthy);
+        assert_eq!(classify_trend(Some(Duration::from_secs(45)), soft, hard, Soft), Soft);
+        assert_eq!(classify_trend(Some(Duration::from_secs(10)), soft, hard, Soft), Hard);
+        // A trend on a healthy box is capped at Soft
+        assert_eq!(classify_trend(Some(Duration::from_secs(10)), soft, hard, Healthy), Soft);
+    }
+
+    #[test]
+    fn test_machine_hysteresis() {
+        use PressureState::*;
+        let dwell = Dwell { soft: Duration::from_secs(10), hard: Duration::from_secs(5) };
+        let mut sm = PressureMachine::new(dwell);
+        let t0 = Instant::now();
+
+        // Rising is immediate
+        let t = sm.update_at(t0, Soft, Healthy, "avail_pct 14.9").unwrap();
+        assert_eq!((t.from, t.to), (Healthy, Soft));
+
+        // Back above the enter threshold but not the exit one: stay
+        assert!(sm.update_at(t0 + Duration::from_secs(20), Healthy, Soft, "avail_pct 16.0").is_none());
+        assert_eq!(sm.state(), Soft);
+
+        // Clear of the exit threshold, but dwell not over yet
+        let mut sm = PressureMachine::new(dwell);
+        sm.update_at(t0, Soft, Soft, "");
+        assert!(sm.update_at(t0 + Duration::from_secs(3), Healthy, Healthy, "").is_none());
+        let t = sm.update_at(t0 + Duration::from_secs(10), Healthy, Healthy, "recovered").unwrap();
+        assert_eq!((t.from, t.to, t.held_secs), (Soft, Healthy, 10));
+    }
+
+    #[test]
+    fn test_machine_steps_down_gradually() {
+        use PressureState::*;
+        let dwell = Dwell { soft: Duration::ZERO, hard: Duration::ZERO };
+        let mut sm = PressureMachine::new(dwell);
+        let t0 = Instant::now();
+        sm.update_at(t0, Hard, Hard, "");
+        // Out of hard by the enter thresholds, still soft by the exit ones
+        let t = sm.update_at(t0, Healthy, Soft, "").unwrap();
+        assert_eq!(t.to, Soft);
+        assert!(sm.update_at(t0, Soft, Soft, "").is_none());
+
+        // Even a full recovery leaves hard through soft
+        sm.update_at(t0, Hard, Hard, "");
+        assert_eq!(sm.update_at(t0, Healthy, Healthy, "").unwrap().to, Soft);
+        assert_eq!(sm.update_at(t0, Healthy, Healthy, "").unwrap().to, Healthy);
+    }
 }
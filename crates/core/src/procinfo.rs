@@ -1,6 +1,13 @@
 use anyhow::Result;
 use procfs::process::all_processes;
-use crate::cgroups::{CgroupInfo, CgroupSlice};
+use crate::cgroups::{CgroupInfo, CgroupSlice, Class, ProtectedUnits, SliceClasses};
+use crate::config::Config;
+use crate::exclude::Exclusions;
+use crate::rules::{Mark, RuleSet, Subject};
+use crate::proctree::{ProcNode, ProcTree};
+use crate::psi::PSIMetrics;
+use crate::util::page_size;
+use std::collections::HashMap;
 use std::fs;
 
 #[derive(Debug, Clone)]
@@ -8,24 +15,201 @@ pub struct ProcLite {
     pub pid: i32,
     pub name: String,
     pub rss_bytes: u64,
+    pub pss_bytes: Option<u64>,
+    pub uss_bytes: Option<u64>,
+    /* Resolved slice or `slice_classes` class */
+    pub class: Option<String>,
+}
+
+/* Memory of one process in bytes. RSS counts every shared page in full for
+ * each process mapping it; PSS splits shared pages between their users and
+ * USS counts only private ones. The smaps_rollup figures (kernel 4.14+) need
+ * ptrace access to the process, so they can be missing. */
+#[derive(Debug, Clone, Default)]
+pub struct MemUsage {
+    pub rss: u64,
+    pub anon: u64,
+    pub file: u64,
+    pub shmem: u64,
+    pub swap: u64,
+    pub pss: Option<u64>,
+    pub uss: Option<u64>,
+    /* Proportional share of swap, like PSS */
+    pub swap_pss: Option<u64>,
+}
+
+impl MemUsage {
+    /* /proc/<pid>/status, plus smaps_rollup where readable. None once the
+     * process is gone. */
+    pub fn read(pid: i32) -> Option<Self> {
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+        let mut usage = parse_status(&status);
+        if let Ok(rollup) = fs::read_to_string(format!("/proc/{}/smaps_rollup", pid)) {
+            usage.add_smaps_rollup(&rollup);
+        }
+        Some(usage)
+    }
+
+    /* What killing the process gives back: its private pages and its share
+     * of swap. Pages shared with other processes stay in use. Without
+     * smaps_rollup, anonymous memory (mostly private) stands in for USS. */
+    pub fn freed_estimate(&self) -> u64 {
+        match self.uss {
+            Some(uss) => uss + self.swap_pss.unwrap_or(self.swap),
+            None => self.anon + self.swap,
+        }
+    }
+
+    fn add_smaps_rollup(&mut self, content: &str) {
+        let mut private = None;
+        for (key, kb) in kb_fields(content) {
+            match key {
+                "Pss" => self.pss = Some(kb * 1024),
+                "Private_Clean" | "Private_Dirty" => *private.get_or_insert(0) += kb * 1024,
+                "SwapPss" => self.swap_pss = Some(kb * 1024),
+                _ => {}
+            }
+        }
+        self.uss = private;
+    }
+}
+
+fn parse_status(content: &str) -> MemUsage {
+    let mut usage = MemUsage::default();
+    for (key, kb) in kb_fields(content) {
+        match key {
+            "VmRSS" => usage.rss = kb * 1024,
+            "RssAnon" => usage.anon = kb * 1024,
+            "RssFile" => usage.file = kb * 1024,
+            "RssShmem" => usage.shmem = kb * 1024,
+            "VmSwap" => usage.swap = kb * 1024,
+            _ => {}
+        }
+    }
+    usage
+}
+
+/* "Key:   1234 kB" lines */
+fn kb_fields(content: &str) -> impl Iterator<Item = (&str, u64)> {
+    content.lines().filter_map(|line| {
+        let (key, rest) = line.split_once(':')?;
+        let value = rest.split_whitespace().next()?.parse().ok()?;
+        Some((key.trim(), value))
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BadnessMode {
+    /* Memory freed by a kill, oom_score_adj, cgroup priority and stall */
+    Sentinel,
+    /* /proc/<pid>/oom_score alone - what the kernel OOM killer would pick */
+    Kernel,
+    /* oom_score plus cgroup priority and stall */
+    Hybrid,
+}
+
+impl BadnessMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "sentinel" => Some(BadnessMode::Sentinel),
+            "kernel" => Some(BadnessMode::Kernel),
+            "hybrid" => Some(BadnessMode::Hybrid),
+            _ => None,
+        }
+    }
+}
+
+/* Everything besides the process itself that goes into its badness.
+ * `rules` and `classes` are compiled once per config load, not per scan. */
+#[derive(Debug)]
+pub struct Scoring<'a> {
+    pub total_mem: u64,
+    pub psi_weight: f64,
+    pub mode: BadnessMode,
+    pub rules: &'a RuleSet,
+    pub classes: &'a SliceClasses,
+}
+
+impl<'a> Scoring<'a> {
+    pub fn new(cfg: &'a Config, rules: &'a RuleSet, classes: &'a SliceClasses, total_mem: u64) -> Self {
+        Self {
+            total_mem,
+            psi_weight: cfg.cgroup_psi_weight,
+            mode: BadnessMode::parse(&cfg.badness_mode).unwrap_or(BadnessMode::Sentinel),
+            rules,
+            classes,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ProcWithBadness {
     pub pid: i32,
     pub name: String,
+    /* From /proc/<pid>/stat, to tell the process from a later one with its PID */
+    pub start_time: u64,
     pub rss_bytes: u64,
     pub oom_score_adj: i32,
+    /* The kernel's own badness, 0..2000: memory, swap and page tables
+     * relative to RAM, shifted by oom_score_adj */
+    pub oom_score: Option<i32>,
     pub cgroup_slice: CgroupSlice,
+    /* `slice_classes` entry, which overrides the slice's priority */
+    pub cgroup_class: Option<Class>,
     pub cgroup_unit: Option<String>,
+    pub cgroup_path: String,
+    /* memory.pressure some_avg10 of the process's cgroup, if readable */
+    pub cgroup_psi_some: Option<f64>,
+    pub mem: MemUsage,
+    /* Set when the process was ranked as the head of its subtree */
+    pub subtree: Option<Subtree>,
+    /* `[[rules]]` that matched, in config order */
+    pub rules_fired: Vec<String>,
+    pub mark: Option<Mark>,
     pub badness_score: f64,
 }
 
+/* Totals for a process and all its descendants */
+#[derive(Debug, Clone, Default)]
+pub struct Subtree {
+    pub procs: usize,
+    pub rss_bytes: u64,
+    pub freed_bytes: u64,
+}
+
 impl ProcWithBadness {
-    /* Composite badness score: RSS percentage + OOM adjustment + cgroup priority.
-     * Higher score = more likely to be killed. */
-    pub fn calculate_badness(&mut self, total_mem: u64) {
-        let rss_score = (self.rss_bytes as f64 / total_mem as f64) * 1000.0;
+    pub fn cgroup(&self) -> CgroupInfo {
+        CgroupInfo {
+            slice: self.cgroup_slice.clone(),
+            class: self.cgroup_class.clone(),
+            unit_name: self.cgroup_unit.clone(),
+            raw_path: self.cgroup_path.clone(),
+        }
+    }
+
+    pub fn cgroup_priority(&self) -> i32 {
+        self.cgroup_class.as_ref().map(|c| c.priority).unwrap_or_else(|| self.cgroup_slice.priority_score())
+    }
+
+    /* Class name if classed, else the slice name */
+    pub fn class_name(&self) -> &str {
+        self.cgroup_class.as_ref().map(|c| c.name.as_str()).unwrap_or_else(|| self.cgroup_slice.name())
+    }
+
+    /* Memory a kill would free: the whole subtree when ranked as one */
+    pub fn freed_bytes(&self) -> u64 {
+        match self.subtree {
+            Some(ref t) => t.freed_bytes,
+            None => self.mem.freed_estimate(),
+        }
+    }
+
+    /* Composite badness score: share of memory a kill would free + OOM
+     * adjustment + cgroup priority + how hard the process's own cgroup is
+     * stalling. The kernel modes start from oom_score instead of the first
+     * two. Higher score = more likely to be killed. */
+    pub fn calculate_badness(&mut self, scoring: &Scoring) {
+        let mem_score = (self.freed_bytes() as f64 / scoring.total_mem as f64) * 1000.0;
         
         // Negative oom_score_adj means "protect me" - reduce impact
         let oom_score = if self.oom_score_adj >= 0 {
@@ -34,93 +218,442 @@ impl ProcWithBadness {
             self.oom_score_adj as f64 * 0.5
         };
         
-        let cgroup_priority = self.cgroup_slice.priority_score() as f64;
+        let cgroup_priority = self.cgroup_priority() as f64;
+
+        let stall = self.cgroup_psi_some.unwrap_or(0.0) * scoring.psi_weight;
+
+        // oom_score covers the process only; descendants ranked with it are
+        // added on the same per-mille scale
+        let descendants = self.freed_bytes().saturating_sub(self.mem.freed_estimate());
+        let kernel_score = match self.oom_score {
+            Some(score) => score as f64 + (descendants as f64 / scoring.total_mem as f64) * 1000.0,
+            None => mem_score + oom_score,
+        };
         
-        self.badness_score = rss_score + oom_score + cgroup_priority;
+        let score = match scoring.mode {
+            BadnessMode::Sentinel => mem_score + oom_score + cgroup_priority + stall,
+            BadnessMode::Kernel => kernel_score,
+            BadnessMode::Hybrid => kernel_score + cgroup_priority + stall,
+        };
+
+        let verdict = scoring.rules.evaluate(&Subject::new(self.pid, &self.name, &self.cgroup_path));
+        self.badness_score = verdict.adjust(score);
+        self.rules_fired = verdict.fired;
+        self.mark = verdict.mark;
+    }
+
+    pub fn is_sacrificial(&self) -> bool {
+        self.mark == Some(Mark::Sacrificial)
     }
 }
 
-pub fn top_processes(limit: usize, exclude: &[String]) -> Result<Vec<ProcLite>> {
+/* Drop processes a rule marked "never"; sacrificial ones go first, then by
+ * badness */
+fn rank(procs: &mut Vec<ProcWithBadness>) {
+    procs.retain(|p| p.mark != Some(Mark::Never));
+    procs.sort_by(|a, b| {
+        b.is_sacrificial()
+            .cmp(&a.is_sacrificial())
+            .then(b.badness_score.total_cmp(&a.badness_score))
+    });
+}
+
+pub fn top_processes(limit: usize, exclude: &Exclusions, classes: &SliceClasses) -> Result<Vec<ProcLite>> {
+    let page = page_size() as u64;
     let mut procs = Vec::new();
-    for pr_res in all_processes()? {
-        if let Ok(pr) = pr_res {
-            if let Ok(statm) = pr.statm() {
-                let rss_pages = statm.resident as u64;
-                let rss = rss_pages * 4096;
-                let name = pr.stat().map(|s| s.comm).unwrap_or_else(|_| String::from("?"));
-                if exclude.iter().any(|e| name.contains(e)) {
-                    continue;
-                }
-                procs.push(ProcLite { pid: pr.pid(), name, rss_bytes: rss });
+    for pr in all_processes()?.flatten() {
+        if let Ok(statm) = pr.statm() {
+            let rss = statm.resident * page;
+            let name = pr.stat().map(|s| s.comm).unwrap_or_else(|_| String::from("?"));
+            if exclude.matches(pr.pid(), &name) {
+                continue;
             }
+            procs.push(ProcLite { pid: pr.pid(), name, rss_bytes: rss, pss_bytes: None, uss_bytes: None, class: None });
         }
     }
     procs.sort_by_key(|p| std::cmp::Reverse(p.rss_bytes));
     procs.truncate(limit);
+    // smaps_rollup walks every mapping; only read it for the ones shown
+    for p in procs.iter_mut() {
+        if let Some(usage) = MemUsage::read(p.pid) {
+            p.pss_bytes = usage.pss;
+            p.uss_bytes = usage.uss;
+        }
+        p.class = CgroupInfo::for_pid(p.pid as u32).ok().map(|cg| cg.with_classes(classes).class_name().to_string());
+    }
     Ok(procs)
 }
 
 pub fn processes_with_badness(
-    exclude: &[String],
-    protected_units: &[String],
-    total_mem: u64,
+    exclude: &Exclusions,
+    protected: &ProtectedUnits,
+    scoring: &Scoring,
 ) -> Result<Vec<ProcWithBadness>> {
+    let page = page_size() as u64;
     let mut procs = Vec::new();
+    // Many processes share a cgroup; read each memory.pressure once
+    let mut cgroup_psi: HashMap<String, Option<f64>> = HashMap::new();
     
-    for pr_res in all_processes()? {
-        if let Ok(pr) = pr_res {
-            let pid = pr.pid();
+    for pr in all_processes()?.flatten() {
+        let pid = pr.pid();
+        
+        if let Ok(statm) = pr.statm() {
+            let rss = statm.resident * page;
             
-            if let Ok(statm) = pr.statm() {
-                let rss_pages = statm.resident as u64;
-                let rss = rss_pages * 4096;
-                
-                // Skip tiny processes (< 10 MB RSS)
-                if rss < 10 * 1024 * 1024 {
-                    continue;
-                }
-                
-                let name = pr.stat().map(|s| s.comm).unwrap_or_else(|_| String::from("?"));
-                
-                if exclude.iter().any(|e| name.contains(e)) {
-                    continue;
-                }
-                
-                let oom_score_adj = read_oom_score_adj(pid).unwrap_or(0);
-                
-                let cgroup_info = CgroupInfo::for_pid(pid as u32).unwrap_or_else(|_| CgroupInfo {
-                    slice: CgroupSlice::Unknown,
-                    unit_name: None,
-                    raw_path: String::new(),
-                });
-                
-                if cgroup_info.is_protected(protected_units) {
-                    continue;
-                }
-                
-                let mut proc = ProcWithBadness {
-                    pid,
-                    name,
-                    rss_bytes: rss,
-                    oom_score_adj,
-                    cgroup_slice: cgroup_info.slice,
-                    cgroup_unit: cgroup_info.unit_name,
-                    badness_score: 0.0,
-                };
-                
-                proc.calculate_badness(total_mem);
-                procs.push(proc);
+            // Skip tiny processes (< 10 MB RSS)
+            if rss < 10 * 1024 * 1024 {
+                continue;
             }
+            
+            // Exited meanwhile
+            let Ok(stat) = pr.stat() else { continue };
+            
+            if exclude.matches(pid, &stat.comm) {
+                continue;
+            }
+            
+            let Some(mut proc) = gather(pid, stat.comm, stat.starttime, rss, protected, scoring, &mut cgroup_psi) else { continue };
+            proc.calculate_badness(scoring);
+            procs.push(proc);
         }
     }
     
-    procs.sort_by(|a, b| b.badness_score.partial_cmp(&a.badness_score).unwrap());
+    rank(&mut procs);
     Ok(procs)
 }
 
+/* Everything needed to score `pid`, badness still unset. None if it is in
+ * a protected unit or exited meanwhile. */
+fn gather(
+    pid: i32,
+    name: String,
+    start_time: u64,
+    rss: u64,
+    protected: &ProtectedUnits,
+    scoring: &Scoring,
+    cgroup_psi: &mut HashMap<String, Option<f64>>,
+) -> Option<ProcWithBadness> {
+    let oom_score_adj = read_oom_score_adj(pid).unwrap_or(0);
+    let oom_score = read_oom_score(pid).ok();
+
+    let cgroup_info = CgroupInfo::for_pid(pid as u32)
+        .unwrap_or_else(|_| CgroupInfo {
+            slice: CgroupSlice::Unknown,
+            class: None,
+            unit_name: None,
+            raw_path: String::new(),
+        })
+        .with_classes(scoring.classes);
+
+    if cgroup_info.is_protected(protected) {
+        return None;
+    }
+
+    let mem = MemUsage::read(pid)?;
+
+    let psi_some = *cgroup_psi
+        .entry(cgroup_info.raw_path.clone())
+        .or_insert_with(|| cgroup_pressure(&cgroup_info));
+
+    Some(ProcWithBadness {
+        pid,
+        name,
+        start_time,
+        rss_bytes: rss,
+        oom_score_adj,
+        oom_score,
+        cgroup_slice: cgroup_info.slice,
+        cgroup_class: cgroup_info.class,
+        cgroup_unit: cgroup_info.unit_name,
+        cgroup_path: cgroup_info.raw_path,
+        cgroup_psi_some: psi_some,
+        mem,
+        subtree: None,
+        rules_fired: Vec::new(),
+        mark: None,
+        badness_score: 0.0,
+    })
+}
+
+/* Charge each candidate to the subtree it belongs to and rank subtree heads
+ * on the memory of everything below them, so a `make -j` build shows up as
+ * `make` with every compiler it spawned rather than as one compiler. A
+ * subtree extends up through parents in the same cgroup, stopping below
+ * session leaders (shells), excluded processes and processes a rule marks
+ * "never". */
+pub fn group_by_subtree(
+    procs: Vec<ProcWithBadness>,
+    tree: &ProcTree,
+    exclude: &Exclusions,
+    protected: &ProtectedUnits,
+    scoring: &Scoring,
+) -> Vec<ProcWithBadness> {
+    let mut cgroup_psi: HashMap<String, Option<f64>> = HashMap::new();
+    // Better estimates from smaps_rollup where we have them
+    let own: HashMap<i32, u64> = procs.iter().map(|p| (p.pid, p.mem.freed_estimate())).collect();
+    let mut heads: Vec<ProcWithBadness> = Vec::new();
+    // Candidates' cgroups are known; ancestors are read once each
+    let mut cgroups: HashMap<i32, Option<String>> =
+        procs.iter().map(|p| (p.pid, Some(p.cgroup_path.clone()))).collect();
+
+    for p in &procs {
+        let root = tree.subtree_root(p.pid, |parent| {
+            joins_cached(parent, &p.cgroup_path, &mut cgroups, exclude, scoring.rules)
+        });
+        if heads.iter().any(|h| h.pid == root) {
+            continue;
+        }
+        let head = if root == p.pid {
+            Some(p.clone())
+        } else {
+            match procs.iter().find(|q| q.pid == root) {
+                Some(q) => Some(q.clone()),
+                None => tree
+                    .get(root)
+                    .and_then(|n| gather(root, n.name.clone(), n.start_time, n.rss_bytes, protected, scoring, &mut cgroup_psi)),
+            }
+        };
+        let Some(mut head) = head else { continue };
+
+        // Count only what a process-tree kill would signal
+        let members: Vec<&ProcNode> = tree
+            .subtree_where(root, |n| joins_cached(n, &p.cgroup_path, &mut cgroups, exclude, scoring.rules))
+            .into_iter()
+            .filter_map(|pid| tree.get(pid))
+            .collect();
+        head.subtree = Some(Subtree {
+            procs: members.len(),
+            rss_bytes: members.iter().map(|n| n.rss_bytes).sum(),
+            freed_bytes: members.iter().map(|n| own.get(&n.pid).copied().unwrap_or(n.anon_bytes)).sum(),
+        });
+        head.calculate_badness(scoring);
+        heads.push(head);
+    }
+
+    rank(&mut heads);
+    heads
+}
+
+/* Whether `node`, in cgroup `node_cgroup`, belongs with a subtree headed
+ * in `head_cgroup`: the same cgroup (so no protected unit the head isn't
+ * in), not a session leader, not excluded and not marked "never". Decides
+ * both how far a subtree reaches up and which descendants a process-tree
+ * kill signals. */
+pub fn joins_subtree(node: &ProcNode, node_cgroup: &str, head_cgroup: &str, exclude: &Exclusions, rules: &RuleSet) -> bool {
+    node_cgroup == head_cgroup
+        && !node.is_session_leader()
+        && !exclude.matches(node.pid, &node.name)
+        && (rules.is_empty() || rules.evaluate(&Subject::new(node.pid, &node.name, node_cgroup)).mark != Some(Mark::Never))
+}
+
+/* `joins_subtree`, reading each process's cgroup at most once per pass */
+fn joins_cached(
+    node: &ProcNode,
+    head_cgroup: &str,
+    cgroups: &mut HashMap<i32, Option<String>>,
+    exclude: &Exclusions,
+    rules: &RuleSet,
+) -> bool {
+    let cgroup = cgroups
+        .entry(node.pid)
+        .or_insert_with(|| CgroupInfo::for_pid(node.pid as u32).ok().map(|cg| cg.raw_path));
+    match cgroup {
+        Some(cgroup) => joins_subtree(node, cgroup, head_cgroup, exclude, rules),
+        None => false,
+    }
+}
+
+/* The root cgroup's memory.pressure is the system-wide figure, which says
+ * nothing about who is stalling */
+fn cgroup_pressure(cg: &CgroupInfo) -> Option<f64> {
+    if cg.raw_path.trim_matches('/').is_empty() {
+        return None;
+    }
+    PSIMetrics::sample_cgroup(&cg.fs_path()).ok().map(|p| p.some_avg10)
+}
+
+/* The process the kernel OOM killer would pick right now: highest
+ * oom_score over every process, with none of sentinel's exclusions */
+pub fn kernel_oom_choice() -> Result<Option<ProcLite>> {
+    let page = page_size() as u64;
+    let mut best: Option<(i32, ProcLite)> = None;
+    for pr in all_processes()?.flatten() {
+        let Ok(score) = read_oom_score(pr.pid()) else { continue };
+        if score <= 0 || best.as_ref().map(|(b, _)| score <= *b).unwrap_or(false) {
+            continue;
+        }
+        let name = pr.stat().map(|s| s.comm).unwrap_or_else(|_| String::from("?"));
+        let rss = pr.statm().map(|m| m.resident * page).unwrap_or(0);
+        best = Some((score, ProcLite { pid: pr.pid(), name, rss_bytes: rss, pss_bytes: None, uss_bytes: None, class: None }));
+    }
+    Ok(best.map(|(_, p)| p))
+}
+
+fn read_oom_score(pid: i32) -> Result<i32> {
+    let content = fs::read_to_string(format!("/proc/{}/oom_score", pid))?;
+    Ok(content.trim().parse()?)
+}
+
 fn read_oom_score_adj(pid: i32) -> Result<i32> {
     let path = format!("/proc/{}/oom_score_adj", pid);
     let content = fs::read_to_string(path)?;
     Ok(content.trim().parse()?)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(slice: CgroupSlice, rss_mb: u64, stall: Option<f64>) -> ProcWithBadness {
+        let rss = rss_mb * 1024 * 1024;
+        ProcWithBadness {
+            pid: 1234,
+            name: "test".into(),
+            start_time: 0,
+            rss_bytes: rss,
+            oom_score_adj: 0,
+            oom_score: None,
+            cgroup_slice: slice,
+            cgroup_class: None,
+            cgroup_unit: None,
+            cgroup_path: String::new(),
+            cgroup_psi_some: stall,
+            mem: MemUsage { rss, anon: rss, ..Default::default() },
+            subtree: None,
+            rules_fired: Vec::new(),
+            mark: None,
+            badness_score: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_stalling_cgroup_outranks_bigger_session() {
+        let mut scoring = Scoring { total_mem: 8 * 1024 * 1024 * 1024, psi_weight: 10.0, mode: BadnessMode::Sentinel, rules: &RuleSet::default(), classes: &SliceClasses::default() };
+        let mut session = candidate(CgroupSlice::User, 2048, Some(0.5));
+        let mut container = candidate(CgroupSlice::Machine, 1024, Some(40.0));
+        session.calculate_badness(&scoring);
+        container.calculate_badness(&scoring);
+        assert!(container.badness_score > session.badness_score);
+
+        // Without the stall term the bigger user session would go first
+        scoring.psi_weight = 0.0;
+        session.calculate_badness(&scoring);
+        container.calculate_badness(&scoring);
+        assert!(session.badness_score > container.badness_score);
+    }
+
+    #[test]
+    fn test_kernel_scoring_modes() {
+        let mut scoring = Scoring { total_mem: 8 * 1024 * 1024 * 1024, psi_weight: 0.0, mode: BadnessMode::Kernel, rules: &RuleSet::default(), classes: &SliceClasses::default() };
+        // The kernel counts swap and page tables we don't see in RSS
+        let mut service = ProcWithBadness { oom_score: Some(360), ..candidate(CgroupSlice::System, 1024, None) };
+        let mut app = ProcWithBadness { oom_score: Some(300), ..candidate(CgroupSlice::User, 1024, None) };
+        service.calculate_badness(&scoring);
+        app.calculate_badness(&scoring);
+        assert_eq!(service.badness_score, 360.0);
+        assert!(service.badness_score > app.badness_score);
+
+        // Hybrid: the user slice's priority outweighs the difference
+        scoring.mode = BadnessMode::Hybrid;
+        service.calculate_badness(&scoring);
+        app.calculate_badness(&scoring);
+        assert!(app.badness_score > service.badness_score);
+
+        assert_eq!(BadnessMode::parse("hybrid"), Some(BadnessMode::Hybrid));
+        assert_eq!(BadnessMode::parse("rss"), None);
+    }
+
+    const ROLLUP: &str = "55d0c0000000-7ffd00000000 ---p 00000000 00:00 0    [rollup]
+Rss:              409600 kB
+Pss:              153600 kB
+Shared_Clean:     256000 kB
+Shared_Dirty:      51200 kB
+Private_Clean:     20480 kB
+Private_Dirty:     81920 kB
+Swap:              40960 kB
+SwapPss:           10240 kB
+";
+
+    const STATUS: &str = "Name:\tchrome
+VmRSS:\t  409600 kB
+RssAnon:\t  184320 kB
+RssFile:\t  204800 kB
+RssShmem:\t   20480 kB
+VmSwap:\t   40960 kB
+Threads:\t17
+";
+
+    #[test]
+    fn test_freed_estimate_ignores_shared_pages() {
+        let mib = 1024 * 1024;
+        let mut usage = parse_status(STATUS);
+        assert_eq!(usage.rss, 400 * mib);
+        assert_eq!(usage.shmem, 20 * mib);
+        // No smaps_rollup: anon + swap
+        assert_eq!(usage.freed_estimate(), 220 * mib);
+
+        usage.add_smaps_rollup(ROLLUP);
+        assert_eq!(usage.pss, Some(150 * mib));
+        assert_eq!(usage.uss, Some(100 * mib));
+        // Private pages plus its share of swap, a quarter of RSS
+        assert_eq!(usage.freed_estimate(), 110 * mib);
+    }
+
+    #[test]
+    fn test_read_own_usage() {
+        let me = std::process::id() as i32;
+        let usage = MemUsage::read(me).unwrap();
+        assert!(usage.rss > 0);
+        // smaps_rollup can be hidden (containers, hardened /proc); then the
+        // smaps figures are just absent
+        if std::fs::read_to_string(format!("/proc/{}/smaps_rollup", me)).is_ok() {
+            assert!(usage.uss.unwrap() <= usage.rss);
+            assert!(usage.pss.is_some());
+        } else {
+            assert!(usage.uss.is_none());
+        }
+    }
+
+    #[test]
+    fn test_group_by_subtree() {
+        use std::process::{Command, Stdio};
+
+        let mut sh = Command::new("sh")
+            .args(["-c", "sleep 30 & sleep 30 & tail -f /dev/null & wait"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let root = sh.id() as i32;
+        let mut tree = ProcTree::build().unwrap();
+        for _ in 0..100 {
+            if tree.children(root).len() == 3 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            tree = ProcTree::build().unwrap();
+        }
+
+        let cgroup = CgroupInfo::for_pid(root as u32).unwrap().raw_path;
+        let procs: Vec<ProcWithBadness> = tree
+            .children(root)
+            .iter()
+            .filter(|&&pid| tree.get(pid).is_some_and(|n| n.name == "sleep"))
+            .map(|&pid| ProcWithBadness { pid, cgroup_path: cgroup.clone(), ..candidate(CgroupSlice::User, 64, None) })
+            .collect();
+        // Stop below the test binary itself; tail is spared by a tree kill,
+        // so it must not count towards the subtree either
+        let exclude = Exclusions::new(&["glob:sentinel_core*".to_string(), "tail".to_string()]).unwrap();
+        let scoring = Scoring { total_mem: 8 << 30, psi_weight: 0.0, mode: BadnessMode::Sentinel, rules: &RuleSet::default(), classes: &SliceClasses::default() };
+        let heads = group_by_subtree(procs, &tree, &exclude, &ProtectedUnits::default(), &scoring);
+
+        let _ = sh.kill();
+        let _ = sh.wait();
+        assert_eq!(heads.len(), 1);
+        assert_eq!(heads[0].pid, root);
+        let subtree = heads[0].subtree.as_ref().unwrap();
+        assert_eq!(subtree.procs, 3);
+        assert!(subtree.freed_bytes >= 128 << 20);
+    }
+}
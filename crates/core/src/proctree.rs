@@ -0,0 +1,190 @@
+use anyhow::Result;
+use procfs::process::all_processes;
+use std::collections::HashMap;
+
+use crate::util::page_size;
+
+/* Process tree from the ppid links in /proc. Killing one `make -j` child or
+ * one browser renderer frees next to nothing and the parent starts another,
+ * so memory is also summed per subtree. Only stat and statm are read, which
+ * keeps building the tree cheap enough to do under pressure. */
+
+#[derive(Debug, Clone)]
+pub struct ProcNode {
+    pub pid: i32,
+    pub ppid: i32,
+    /* Session ID; equal to `pid` for session leaders (login and terminal shells) */
+    pub session: i32,
+    /* Clock ticks after boot; tells a recycled PID from the original */
+    pub start_time: u64,
+    pub name: String,
+    pub rss_bytes: u64,
+    /* Resident minus shared pages, i.e. RssAnon: roughly what a kill frees */
+    pub anon_bytes: u64,
+}
+
+impl ProcNode {
+    pub fn is_session_leader(&self) -> bool {
+        self.pid == self.session
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ProcTree {
+    nodes: HashMap<i32, ProcNode>,
+    children: HashMap<i32, Vec<i32>>,
+}
+
+impl ProcTree {
+    pub fn build() -> Result<Self> {
+        let page = page_size() as u64;
+        let mut nodes = Vec::new();
+        for pr in all_processes()?.flatten() {
+            let Ok(stat) = pr.stat() else { continue };
+            let (rss, anon) = pr
+                .statm()
+                .map(|m| (m.resident * page, m.resident.saturating_sub(m.shared) * page))
+                .unwrap_or((0, 0));
+            nodes.push(ProcNode {
+                pid: stat.pid,
+                ppid: stat.ppid,
+                session: stat.session,
+                start_time: stat.starttime,
+                name: stat.comm,
+                rss_bytes: rss,
+                anon_bytes: anon,
+            });
+        }
+        Ok(Self::from_nodes(nodes))
+    }
+
+    pub fn from_nodes(nodes: Vec<ProcNode>) -> Self {
+        let mut tree = ProcTree::default();
+        for node in nodes {
+            if node.ppid != node.pid {
+                tree.children.entry(node.ppid).or_default().push(node.pid);
+            }
+            tree.nodes.insert(node.pid, node);
+        }
+        for kids in tree.children.values_mut() {
+            kids.sort_unstable();
+        }
+        tree
+    }
+
+    pub fn get(&self, pid: i32) -> Option<&ProcNode> {
+        self.nodes.get(&pid)
+    }
+
+    pub fn children(&self, pid: i32) -> &[i32] {
+        self.children.get(&pid).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /* `pid` and everything below it, children before their parent, so
+     * signalling in this order never leaves a parent around to respawn */
+    pub fn subtree_leaves_first(&self, pid: i32) -> Vec<i32> {
+        self.subtree_where(pid, |_| true)
+    }
+
+    /* Like `subtree_leaves_first`, but a descendant failing `keep` is left
+     * out together with everything below it. `pid` itself is always in. */
+    pub fn subtree_where(&self, pid: i32, mut keep: impl FnMut(&ProcNode) -> bool) -> Vec<i32> {
+        let mut out = Vec::new();
+        // (pid, children already pushed)
+        let mut stack = vec![(pid, false)];
+        while let Some((p, expanded)) = stack.pop() {
+            if expanded {
+                out.push(p);
+                continue;
+            }
+            stack.push((p, true));
+            for &c in self.children(p).iter().rev() {
+                if self.get(c).map(&mut keep).unwrap_or(false) {
+                    stack.push((c, false));
+                }
+            }
+        }
+        out
+    }
+
+    /* Sum `f` over the subtree rooted at `pid` */
+    pub fn subtree_sum(&self, pid: i32, f: impl Fn(&ProcNode) -> u64) -> u64 {
+        self.subtree_leaves_first(pid).iter().filter_map(|p| self.get(*p)).map(f).sum()
+    }
+
+    /* Walk up from `pid` while `joins(parent)` allows; the last process
+     * reached heads the subtree `pid` is charged to */
+    pub fn subtree_root(&self, pid: i32, mut joins: impl FnMut(&ProcNode) -> bool) -> i32 {
+        let mut root = pid;
+        while let Some(parent) = self.get(root).and_then(|n| self.get(n.ppid)) {
+            // pid 1 and kthreadd head everything
+            if parent.pid <= 2 || parent.pid == root || !joins(parent) {
+                break;
+            }
+            root = parent.pid;
+        }
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(pid: i32, ppid: i32, session: i32, name: &str, anon_mb: u64) -> ProcNode {
+        ProcNode {
+            pid,
+            ppid,
+            session,
+            start_time: pid as u64,
+            name: name.into(),
+            rss_bytes: (anon_mb * 2) << 20,
+            anon_bytes: anon_mb << 20,
+        }
+    }
+
+    /* init -> bash (session leader) -> make -> {sh -> cc1, sh -> cc1} */
+    fn build_tree() -> ProcTree {
+        ProcTree::from_nodes(vec![
+            node(1, 0, 1, "systemd", 10),
+            node(100, 1, 100, "bash", 5),
+            node(200, 100, 100, "make", 20),
+            node(300, 200, 100, "sh", 1),
+            node(301, 300, 100, "cc1plus", 400),
+            node(302, 200, 100, "sh", 1),
+            node(303, 302, 100, "cc1plus", 300),
+        ])
+    }
+
+    #[test]
+    fn test_leaves_first() {
+        let tree = build_tree();
+        let order = tree.subtree_leaves_first(200);
+        assert_eq!(order, vec![301, 300, 303, 302, 200]);
+        assert_eq!(tree.subtree_leaves_first(301), vec![301]);
+        assert_eq!(tree.subtree_sum(200, |n| n.anon_bytes), 722 << 20);
+        // A pruned child takes its own children with it
+        assert_eq!(tree.subtree_where(200, |n| n.pid != 300), vec![303, 302, 200]);
+        assert_eq!(tree.subtree_where(200, |_| false), vec![200]);
+    }
+
+    #[test]
+    fn test_subtree_root_stops_at_session_leader() {
+        let tree = build_tree();
+        let root = tree.subtree_root(303, |p| !p.is_session_leader());
+        assert_eq!(root, 200);
+        // Nothing joins: every process is its own subtree
+        assert_eq!(tree.subtree_root(303, |_| false), 303);
+        // Never climbs to init
+        assert_eq!(tree.subtree_root(303, |_| true), 100);
+    }
+
+    #[test]
+    fn test_build_live_tree() {
+        let tree = ProcTree::build().unwrap();
+        let me = std::process::id() as i32;
+        let node = tree.get(me).unwrap();
+        assert!(node.rss_bytes > 0);
+        assert!(tree.children(node.ppid).contains(&me));
+    }
+}
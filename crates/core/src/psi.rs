@@ -1,6 +1,10 @@
-use anyhow::{Context, Result};
-use std::fs;
+use anyhow::{bail, Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
+use std::time::Duration;
 
 /* Pressure Stall Information - requires kernel 4.20+ */
 
@@ -29,6 +33,16 @@ impl PSIMetrics {
         Self::parse(&content)
     }
 
+    /* Same numbers for one cgroup, from its memory.pressure (cgroup v2).
+     * Stalls of every task below `cgroup_dir` are counted. */
+    pub fn sample_cgroup(cgroup_dir: &Path) -> Result<Self> {
+        let path = cgroup_dir.join("memory.pressure");
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        Self::parse(&content)
+    }
+
     fn parse(content: &str) -> Result<Self> {
         let mut metrics = PSIMetrics::default();
         
@@ -66,6 +80,69 @@ impl PSIMetrics {
     }
 }
 
+/* Kernel PSI trigger (5.2+): the kernel wakes us once `stall` of memory
+ * stall time accumulates within any `window`, instead of us finding out at
+ * the next scan. The trigger lives as long as the fd stays open. */
+#[derive(Debug)]
+pub struct PsiTrigger {
+    file: File,
+    spec: String,
+}
+
+impl PsiTrigger {
+    /* `kind` is "some" or "full" */
+    pub fn new(kind: &str, stall: Duration, window: Duration) -> Result<Self> {
+        let spec = Self::spec(kind, stall, window)?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/proc/pressure/memory")
+            .context("failed to open /proc/pressure/memory")?;
+        // The kernel expects the terminating NUL
+        file.write_all(format!("{}\0", spec).as_bytes())
+            .with_context(|| format!("kernel rejected PSI trigger \"{}\" (needs 5.2+)", spec))?;
+        Ok(Self { file, spec })
+    }
+
+    /* Trigger line as written to the pressure file, within the kernel's limits */
+    pub fn spec(kind: &str, stall: Duration, window: Duration) -> Result<String> {
+        if kind != "some" && kind != "full" {
+            bail!("PSI trigger kind must be \"some\" or \"full\", got {:?}", kind);
+        }
+        if window < Duration::from_millis(500) || window > Duration::from_secs(10) {
+            bail!("PSI trigger window must be between 500ms and 10s, got {:?}", window);
+        }
+        if stall.is_zero() || stall > window {
+            bail!("PSI trigger stall must be non-zero and at most the window, got {:?}", stall);
+        }
+        Ok(format!("{} {} {}", kind, stall.as_micros(), window.as_micros()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.spec
+    }
+
+    /* Block until the trigger fires (true) or `timeout` passes (false).
+     * `None` waits forever. */
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<bool> {
+        let mut pfd = libc::pollfd { fd: self.file.as_raw_fd(), events: libc::POLLPRI, revents: 0 };
+        let ms = timeout.map(|t| t.as_millis().min(i32::MAX as u128) as i32).unwrap_or(-1);
+        let rc = unsafe { libc::poll(&mut pfd, 1, ms) };
+        if rc < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                return Ok(false);
+            }
+            return Err(err).context("poll on PSI trigger failed");
+        }
+        if pfd.revents & libc::POLLERR != 0 {
+            bail!("PSI trigger is no longer valid");
+        }
+        Ok(pfd.revents & libc::POLLPRI != 0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,4 +175,32 @@ full avg10=0.00 avg60=0.00 avg300=0.00 total=0
         assert_eq!(metrics.some_avg10, 0.0);
         assert_eq!(metrics.full_avg10, 0.0);
     }
+
+    #[test]
+    fn test_sample_cgroup() {
+        let dir = std::env::temp_dir().join(format!("sentinel-psi-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("memory.pressure"),
+            "some avg10=42.00 avg60=10.00 avg300=2.00 total=999\nfull avg10=20.00 avg60=5.00 avg300=1.00 total=500\n",
+        ).unwrap();
+
+        let metrics = PSIMetrics::sample_cgroup(&dir).unwrap();
+        assert_eq!(metrics.some_avg10, 42.0);
+        assert_eq!(metrics.full_avg10, 20.0);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(PSIMetrics::sample_cgroup(&dir).is_err());
+    }
+
+    #[test]
+    fn test_trigger_spec() {
+        let spec = PsiTrigger::spec("some", Duration::from_millis(150), Duration::from_secs(1)).unwrap();
+        assert_eq!(spec, "some 150000 1000000");
+
+        assert!(PsiTrigger::spec("half", Duration::from_millis(150), Duration::from_secs(1)).is_err());
+        assert!(PsiTrigger::spec("some", Duration::from_millis(150), Duration::from_millis(100)).is_err());
+        assert!(PsiTrigger::spec("full", Duration::from_secs(2), Duration::from_secs(1)).is_err());
+        assert!(PsiTrigger::spec("full", Duration::ZERO, Duration::from_secs(1)).is_err());
+    }
 }
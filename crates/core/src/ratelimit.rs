@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/* Sliding one-minute window over process actions (freeze, kill).
+ * The normal budget is `max_actions_per_min`. When PSI full_avg10 shows the
+ * whole system stalling, a separate emergency budget may be drawn on once the
+ * normal one is spent, so a real lockup is never ignored while a slow leak
+ * can't trigger a kill spree. */
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permit {
+    Normal,
+    Emergency,
+}
+
+#[derive(Debug)]
+pub struct ActionLimiter {
+    max_per_min: u32,
+    emergency_max_per_min: u32,
+    normal: VecDeque<Instant>,
+    emergency: VecDeque<Instant>,
+}
+
+impl ActionLimiter {
+    pub fn new(max_per_min: u32, emergency_max_per_min: u32) -> Self {
+        Self {
+            max_per_min,
+            emergency_max_per_min,
+            normal: VecDeque::new(),
+            emergency: VecDeque::new(),
+        }
+    }
+
+    /* Apply new limits after a config reload, keeping recent history */
+    pub fn set_limits(&mut self, max_per_min: u32, emergency_max_per_min: u32) {
+        self.max_per_min = max_per_min;
+        self.emergency_max_per_min = emergency_max_per_min;
+    }
+
+    pub fn remaining(&mut self) -> u32 {
+        self.remaining_at(Instant::now())
+    }
+
+    pub fn emergency_remaining(&mut self) -> u32 {
+        self.emergency_remaining_at(Instant::now())
+    }
+
+    /* Take one action slot. `stalled` unlocks the emergency budget. */
+    pub fn try_acquire(&mut self, stalled: bool) -> Option<Permit> {
+        self.try_acquire_at(Instant::now(), stalled)
+    }
+
+    fn remaining_at(&mut self, now: Instant) -> u32 {
+        expire(&mut self.normal, now);
+        self.max_per_min.saturating_sub(self.normal.len() as u32)
+    }
+
+    fn emergency_remaining_at(&mut self, now: Instant) -> u32 {
+        expire(&mut self.emergency, now);
+        self.emergency_max_per_min.saturating_sub(self.emergency.len() as u32)
+    }
+
+    fn try_acquire_at(&mut self, now: Instant, stalled: bool) -> Option<Permit> {
+        if self.remaining_at(now) > 0 {
+            self.normal.push_back(now);
+            return Some(Permit::Normal);
+        }
+        if stalled && self.emergency_remaining_at(now) > 0 {
+            self.emergency.push_back(now);
+            return Some(Permit::Emergency);
+        }
+        None
+    }
+}
+
+fn expire(window: &mut VecDeque<Instant>, now: Instant) {
+    while let Some(&t) = window.front() {
+        if now.saturating_duration_since(t) >= WINDOW {
+            window.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_exhausts_and_refills() {
+        let mut rl = ActionLimiter::new(2, 0);
+        let t0 = Instant::now();
+
+        assert_eq!(rl.try_acquire_at(t0, false), Some(Permit::Normal));
+        assert_eq!(rl.try_acquire_at(t0 + Duration::from_secs(10), false), Some(Permit::Normal));
+        assert_eq!(rl.remaining_at(t0 + Duration::from_secs(20)), 0);
+        assert_eq!(rl.try_acquire_at(t0 + Duration::from_secs(20), false), None);
+
+        // First slot expires a minute after it was taken
+        assert_eq!(rl.remaining_at(t0 + Duration::from_secs(60)), 1);
+        assert_eq!(rl.try_acquire_at(t0 + Duration::from_secs(60), false), Some(Permit::Normal));
+        assert_eq!(rl.try_acquire_at(t0 + Duration::from_secs(61), false), None);
+    }
+
+    #[test]
+    fn test_emergency_override() {
+        let mut rl = ActionLimiter::new(1, 1);
+        let t0 = Instant::now();
+
+        assert_eq!(rl.try_acquire_at(t0, true), Some(Permit::Normal));
+        // Normal budget spent: only a stalled system may use the emergency slot
+        assert_eq!(rl.try_acquire_at(t0, false), None);
+        assert_eq!(rl.try_acquire_at(t0, true), Some(Permit::Emergency));
+        assert_eq!(rl.try_acquire_at(t0, true), None);
+        assert_eq!(rl.emergency_remaining_at(t0), 0);
+    }
+
+    #[test]
+    fn test_zero_budget() {
+        let mut rl = ActionLimiter::new(0, 0);
+        assert_eq!(rl.try_acquire(true), None);
+        assert_eq!(rl.remaining(), 0);
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+
+use crate::actions;
+use crate::cgroups::{CgroupInfo, CgroupSlice, ProtectedUnits};
+use crate::procinfo::ProcWithBadness;
+
+/* Proactive reclaim through cgroup v2 memory.reclaim (kernel 5.19+): ask a
+ * low-priority cgroup to give back memory - page cache first, anon to swap -
+ * before anything has to be frozen or killed. The kernel reports only
+ * whether it got the full amount, so what was really freed is measured from
+ * memory.current. */
+
+/* Requests smaller than this aren't worth a reclaim pass */
+const MIN_REQUEST: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Reclaimed {
+    pub cgroup: CgroupInfo,
+    pub requested: u64,
+    /* Drop in memory.current across the request */
+    pub reclaimed: u64,
+    /* The kernel reclaimed the full request */
+    pub complete: bool,
+}
+
+pub fn supported(cgroup: &CgroupInfo) -> bool {
+    cgroup.fs_path().join("memory.reclaim").exists()
+}
+
+/* Slices that only hold user sessions and guests, plus any configured unit */
+pub fn eligible(cgroup: &CgroupInfo, units: &[String]) -> bool {
+    match cgroup.slice {
+        CgroupSlice::User | CgroupSlice::Machine => true,
+        _ => cgroup.unit_name.as_ref().map(|u| units.contains(u)).unwrap_or(false),
+    }
+}
+
+/* Cgroups to reclaim from, lowest priority slice first and by badness
+ * within a slice. Only init, the root and protected units are left alone:
+ * login sessions often hold most of what can be reclaimed. */
+pub fn targets(procs: &[ProcWithBadness], units: &[String], protected: &ProtectedUnits) -> Vec<CgroupInfo> {
+    let mut out: Vec<CgroupInfo> = Vec::new();
+    for p in procs {
+        if out.iter().any(|cg| cg.raw_path == p.cgroup_path) {
+            continue;
+        }
+        let cg = p.cgroup();
+        if eligible(&cg, units) && cg.unit_veto(protected).is_none() && supported(&cg) {
+            out.push(cg);
+        }
+    }
+    // Stable: keeps badness order inside a slice
+    out.sort_by_key(|cg| std::cmp::Reverse(cg.priority()));
+    out
+}
+
+/* How much to ask of a group using `usage` bytes: what is still needed, but
+ * no more than `step_pct` percent of its usage in one go */
+pub fn request_bytes(usage: u64, needed: u64, step_pct: u8) -> u64 {
+    let request = needed.min(usage.saturating_mul(step_pct as u64) / 100);
+    if request < MIN_REQUEST { 0 } else { request }
+}
+
+pub fn reclaim(cgroup: &CgroupInfo, needed: u64, step_pct: u8) -> Result<Reclaimed> {
+    let usage = memory_current(cgroup)?;
+    let requested = request_bytes(usage, needed, step_pct);
+    if requested == 0 {
+        return Ok(Reclaimed { cgroup: cgroup.clone(), requested, reclaimed: 0, complete: true });
+    }
+    let complete = actions::write_memory_reclaim(&cgroup.fs_path(), requested)?;
+    let after = memory_current(cgroup)?;
+    Ok(Reclaimed { cgroup: cgroup.clone(), requested, reclaimed: usage.saturating_sub(after), complete })
+}
+
+fn memory_current(cgroup: &CgroupInfo) -> Result<u64> {
+    cgroup.read_value("memory.current")?.parse().context("parsing memory.current")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proc_in(path: &str, badness: f64) -> ProcWithBadness {
+        let cg = CgroupInfo::for_path(path);
+        ProcWithBadness {
+            pid: 1,
+            name: "p".into(),
+            start_time: 0,
+            rss_bytes: 0,
+            oom_score_adj: 0,
+            oom_score: None,
+            cgroup_slice: cg.slice,
+            cgroup_class: cg.class,
+            cgroup_unit: cg.unit_name,
+            cgroup_path: cg.raw_path,
+            cgroup_psi_some: None,
+            mem: Default::default(),
+            subtree: None,
+            rules_fired: Vec::new(),
+            mark: None,
+            badness_score: badness,
+        }
+    }
+
+    #[test]
+    fn test_eligible() {
+        let units = vec!["backup.service".to_string()];
+        assert!(eligible(&CgroupInfo::for_path("/user.slice/user-1000.slice/user@1000.service/app.slice/app-x.scope"), &units));
+        assert!(eligible(&CgroupInfo::for_path("/machine.slice/machine-vm.scope"), &units));
+        assert!(eligible(&CgroupInfo::for_path("/system.slice/backup.service"), &units));
+        assert!(!eligible(&CgroupInfo::for_path("/system.slice/postgresql.service"), &units));
+        assert!(!eligible(&CgroupInfo::for_path("/init.scope"), &units));
+
+        // Nothing here has memory.reclaim outside a real cgroup tree
+        let procs = vec![proc_in("/system.slice/postgresql.service", 500.0)];
+        assert!(targets(&procs, &units, &ProtectedUnits::default()).is_empty());
+    }
+
+    #[test]
+    fn test_request_bytes() {
+        let mib = 1024 * 1024;
+        // Capped at the step
+        assert_eq!(request_bytes(1000 * mib, 500 * mib, 10), 100 * mib);
+        // Only what is still needed
+        assert_eq!(request_bytes(1000 * mib, 20 * mib, 10), 20 * mib);
+        // Too small to bother
+        assert_eq!(request_bytes(5 * mib, 100 * mib, 10), 0);
+    }
+}
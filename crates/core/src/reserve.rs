@@ -1,24 +1,166 @@
-use std::cell::RefCell;
+use anyhow::{anyhow, Result};
+use std::ptr;
 
-thread_local! {
-    static RESERVE: RefCell<Option<Vec<u8>>> = RefCell::new(None);
+use crate::util::page_size;
+
+/* Memory reserve balloon. The reserve is split into equal chunks of mmap'd
+ * anonymous memory with every page written at map time, so it is really
+ * resident (a plain zeroed Vec would only map the shared zero page). With
+ * `lock` each chunk is also mlocked so it can't quietly be swapped out.
+ * Chunks are released and re-inflated one at a time so the daemon can give
+ * back headroom in proportion to how deep the pressure is. */
+
+#[derive(Debug)]
+struct Region {
+    ptr: *mut u8,
+    len: usize,
+    locked: bool,
 }
 
-pub fn hold(megabytes: u64) {
-    let bytes = (megabytes as usize) * 1024 * 1024;
-    RESERVE.with(|r| {
-        let mut slot = r.borrow_mut();
-        *slot = Some(vec![0u8; bytes]);
-    });
+impl Region {
+    fn map(len: usize, lock: bool) -> Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(anyhow!("mmap of {} byte reserve chunk failed: {}", len, std::io::Error::last_os_error()));
+        }
+        let mut region = Region { ptr: ptr as *mut u8, len, locked: false };
+
+        // Write every page so the kernel backs it with real memory
+        let page = page_size();
+        let mut off = 0;
+        while off < len {
+            unsafe { ptr::write_volatile(region.ptr.add(off), 0xA5) };
+            off += page;
+        }
+
+        if lock {
+            region.locked = unsafe { libc::mlock(region.ptr as *const libc::c_void, len) } == 0;
+        }
+        Ok(region)
+    }
+
+    fn resident_bytes(&self) -> u64 {
+        let page = page_size();
+        let mut vec = vec![0u8; self.len.div_ceil(page)];
+        let rc = unsafe { libc::mincore(self.ptr as *mut libc::c_void, self.len, vec.as_mut_ptr()) };
+        if rc != 0 {
+            return 0;
+        }
+        vec.iter().filter(|b| *b & 1 != 0).count() as u64 * page as u64
+    }
 }
 
-pub fn release() {
-    RESERVE.with(|r| {
-        let mut slot = r.borrow_mut();
-        *slot = None;
-    });
+impl Drop for Region {
+    fn drop(&mut self) {
+        unsafe {
+            if self.locked {
+                libc::munlock(self.ptr as *const libc::c_void, self.len);
+            }
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
 }
 
-pub fn is_held() -> bool {
-    RESERVE.with(|r| r.borrow().is_some())
+#[derive(Debug)]
+pub struct Reserve {
+    chunks: Vec<Region>,
+    chunk_bytes: usize,
+    total_chunks: usize,
+    lock: bool,
+}
+
+impl Reserve {
+    pub fn new(megabytes: u64, chunks: u32, lock: bool) -> Self {
+        let mut r = Self { chunks: Vec::new(), chunk_bytes: 0, total_chunks: 0, lock };
+        r.set_layout(megabytes, chunks, lock);
+        r
+    }
+
+    /* Change size, chunk count or locking. Returns true if the layout changed,
+     * in which case everything held so far was released. */
+    pub fn set_layout(&mut self, megabytes: u64, chunks: u32, lock: bool) -> bool {
+        let total_chunks = chunks.max(1) as usize;
+        let page = page_size();
+        let bytes = (megabytes as usize) * 1024 * 1024;
+        let chunk_bytes = (bytes / total_chunks).div_ceil(page) * page;
+
+        if chunk_bytes == self.chunk_bytes && total_chunks == self.total_chunks && lock == self.lock {
+            return false;
+        }
+        self.release();
+        self.chunk_bytes = chunk_bytes;
+        self.total_chunks = if chunk_bytes == 0 { 0 } else { total_chunks };
+        self.lock = lock;
+        true
+    }
+
+    /* Inflate every missing chunk. A failed mlock is not fatal: the balloon
+     * is still resident, just swappable - check `is_locked`. */
+    pub fn hold(&mut self) -> Result<()> {
+        let missing = self.total_chunks - self.chunks.len();
+        self.inflate(missing)?;
+        Ok(())
+    }
+
+    /* Map up to `n` more chunks; returns how many were added */
+    pub fn inflate(&mut self, n: usize) -> Result<usize> {
+        let n = n.min(self.total_chunks - self.chunks.len());
+        for _ in 0..n {
+            self.chunks.push(Region::map(self.chunk_bytes, self.lock)?);
+        }
+        Ok(n)
+    }
+
+    /* Unmap up to `n` chunks; returns how many were released */
+    pub fn deflate(&mut self, n: usize) -> usize {
+        let n = n.min(self.chunks.len());
+        self.chunks.truncate(self.chunks.len() - n);
+        n
+    }
+
+    pub fn release(&mut self) {
+        self.chunks.clear();
+    }
+
+    pub fn is_held(&self) -> bool {
+        !self.chunks.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.chunks.len() == self.total_chunks
+    }
+
+    pub fn held_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn total_chunks(&self) -> usize {
+        self.total_chunks
+    }
+
+    pub fn chunk_bytes(&self) -> u64 {
+        self.chunk_bytes as u64
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.is_held() && self.chunks.iter().all(|r| r.locked)
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        (self.chunks.len() * self.chunk_bytes) as u64
+    }
+
+    /* Bytes of the balloon actually in RAM right now, per mincore(2) */
+    pub fn resident_bytes(&self) -> u64 {
+        self.chunks.iter().map(|r| r.resident_bytes()).sum()
+    }
 }
@@ -1,11 +1,47 @@
 mod tests {
-    use crate::reserve;
+    use crate::reserve::Reserve;
 
     #[test]
     fn test_reserve_hold_release() {
-        reserve::hold(1);
-        assert!(reserve::is_held());
-        reserve::release();
-        assert!(!reserve::is_held());
+        let mut r = Reserve::new(1, 1, false);
+        r.hold().unwrap();
+        assert!(r.is_held());
+        r.release();
+        assert!(!r.is_held());
+    }
+
+    #[test]
+    fn test_reserve_is_resident() {
+        let mut r = Reserve::new(4, 2, false);
+        r.hold().unwrap();
+        assert_eq!(r.size_bytes(), 4 * 1024 * 1024);
+        // Every page was touched, so the whole balloon is in RAM
+        assert_eq!(r.resident_bytes(), r.size_bytes());
+        r.release();
+        assert_eq!(r.resident_bytes(), 0);
+    }
+
+    #[test]
+    fn test_reserve_chunks() {
+        let mut r = Reserve::new(8, 4, false);
+        assert_eq!(r.total_chunks(), 4);
+        assert_eq!(r.chunk_bytes(), 2 * 1024 * 1024);
+        r.hold().unwrap();
+        assert!(r.is_full());
+
+        assert_eq!(r.deflate(3), 3);
+        assert_eq!(r.held_chunks(), 1);
+        assert_eq!(r.size_bytes(), 2 * 1024 * 1024);
+        assert_eq!(r.deflate(5), 1);
+        assert!(!r.is_held());
+
+        assert_eq!(r.inflate(10).unwrap(), 4);
+        assert!(r.is_full());
+
+        // Same layout keeps what is held, a new one starts empty
+        assert!(!r.set_layout(8, 4, false));
+        assert!(r.is_full());
+        assert!(r.set_layout(16, 4, false));
+        assert!(!r.is_held());
     }
 }
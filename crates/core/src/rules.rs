@@ -0,0 +1,279 @@
+use anyhow::{bail, Context, Result};
+use glob::Pattern;
+use procfs::process::Process;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::path::PathBuf;
+
+/* `[[rules]]`: adjust the badness of matching processes. Every matcher a
+ * rule sets must match; all matching rules apply in order, multipliers
+ * before offsets. Regexes and globs are compiled and user names resolved
+ * when the config is loaded, so a bad rule fails the load instead of
+ * silently never matching. */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mark {
+    /* Ranked ahead of every unmarked process */
+    Sacrificial,
+    /* Never a victim, like `exclude_names` */
+    Never,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    /* Shown in `simulate --explain`; defaults to `rules[<index>]` */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /* Exact comm, as in /proc/<pid>/comm (at most 15 characters) */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comm: Option<String>,
+    /* Regex searched in the NUL-separated cmdline joined with spaces */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmdline: Option<String>,
+    /* Glob on the executable's full path */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>,
+    /* Numeric uid or user name */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /* Glob on the cgroup path; `*` also matches `/` */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplier: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark: Option<Mark>,
+}
+
+#[derive(Debug)]
+struct Compiled {
+    label: String,
+    comm: Option<String>,
+    cmdline: Option<Regex>,
+    exe: Option<Pattern>,
+    uid: Option<u32>,
+    cgroup: Option<Pattern>,
+    multiplier: f64,
+    offset: f64,
+    mark: Option<Mark>,
+}
+
+impl Compiled {
+    fn new(index: usize, rule: &Rule) -> Result<Self> {
+        let label = rule.name.clone().unwrap_or_else(|| format!("rules[{}]", index));
+        if rule.comm.is_none() && rule.cmdline.is_none() && rule.exe.is_none() && rule.user.is_none() && rule.cgroup.is_none() {
+            bail!("{}: no matcher set (comm, cmdline, exe, user or cgroup)", label);
+        }
+        if rule.multiplier.is_none() && rule.offset.is_none() && rule.mark.is_none() {
+            bail!("{}: no effect set (multiplier, offset or mark)", label);
+        }
+        if rule.multiplier.map(|m| !m.is_finite() || m < 0.0).unwrap_or(false) {
+            bail!("{}: multiplier must be a non-negative number", label);
+        }
+        if rule.offset.map(|o| !o.is_finite()).unwrap_or(false) {
+            bail!("{}: offset must be a finite number", label);
+        }
+        let cmdline = rule.cmdline.as_deref()
+            .map(Regex::new).transpose()
+            .with_context(|| format!("{}: invalid cmdline regex", label))?;
+        let exe = rule.exe.as_deref()
+            .map(Pattern::new).transpose()
+            .with_context(|| format!("{}: invalid exe glob", label))?;
+        let cgroup = rule.cgroup.as_deref()
+            .map(Pattern::new).transpose()
+            .with_context(|| format!("{}: invalid cgroup glob", label))?;
+        let uid = match rule.user.as_deref() {
+            None => None,
+            Some(u) => match u.parse::<u32>() {
+                Ok(uid) => Some(uid),
+                Err(_) => match users::get_user_by_name(u) {
+                    Some(user) => Some(user.uid()),
+                    None => bail!("{}: unknown user {:?}", label, u),
+                },
+            },
+        };
+        Ok(Self {
+            label,
+            comm: rule.comm.clone(),
+            cmdline,
+            exe,
+            uid,
+            cgroup,
+            multiplier: rule.multiplier.unwrap_or(1.0),
+            offset: rule.offset.unwrap_or(0.0),
+            mark: rule.mark,
+        })
+    }
+
+    fn matches(&self, s: &Subject) -> bool {
+        // Cheap fields first; cmdline, exe and uid cost a /proc read
+        if let Some(ref comm) = self.comm {
+            if s.comm != comm {
+                return false;
+            }
+        }
+        if let Some(ref glob) = self.cgroup {
+            if !glob.matches(s.cgroup) {
+                return false;
+            }
+        }
+        if let Some(uid) = self.uid {
+            if s.uid() != Some(uid) {
+                return false;
+            }
+        }
+        if let Some(ref glob) = self.exe {
+            if !s.exe().map(|p| glob.matches_path(p)).unwrap_or(false) {
+                return false;
+            }
+        }
+        if let Some(ref re) = self.cmdline {
+            if !s.cmdline().map(|c| re.is_match(c)).unwrap_or(false) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/* What a process is matched against. cmdline, exe and uid are read from
+ * /proc on first use, so rules that don't need them cost nothing. */
+pub struct Subject<'a> {
+    pid: i32,
+    comm: &'a str,
+    cgroup: &'a str,
+    cmdline: OnceCell<Option<String>>,
+    exe: OnceCell<Option<PathBuf>>,
+    uid: OnceCell<Option<u32>>,
+}
+
+impl<'a> Subject<'a> {
+    pub fn new(pid: i32, comm: &'a str, cgroup: &'a str) -> Self {
+        Self { pid, comm, cgroup, cmdline: OnceCell::new(), exe: OnceCell::new(), uid: OnceCell::new() }
+    }
+
+    fn cmdline(&self) -> Option<&str> {
+        self.cmdline
+            .get_or_init(|| Process::new(self.pid).and_then(|p| p.cmdline()).ok().map(|args| args.join(" ")))
+            .as_deref()
+    }
+
+    fn exe(&self) -> Option<&PathBuf> {
+        self.exe.get_or_init(|| Process::new(self.pid).and_then(|p| p.exe()).ok()).as_ref()
+    }
+
+    fn uid(&self) -> Option<u32> {
+        *self.uid.get_or_init(|| Process::new(self.pid).and_then(|p| p.uid()).ok())
+    }
+}
+
+/* Combined effect of the rules that matched one process */
+#[derive(Debug, Clone, Default)]
+pub struct Verdict {
+    pub fired: Vec<String>,
+    pub multiplier: f64,
+    pub offset: f64,
+    /* `Never` wins over `Sacrificial` */
+    pub mark: Option<Mark>,
+}
+
+impl Verdict {
+    pub fn adjust(&self, score: f64) -> f64 {
+        score * self.multiplier + self.offset
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<Compiled>,
+}
+
+impl RuleSet {
+    pub fn new(rules: &[Rule]) -> Result<Self> {
+        let rules = rules.iter().enumerate().map(|(i, r)| Compiled::new(i, r)).collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn evaluate(&self, subject: &Subject) -> Verdict {
+        let mut verdict = Verdict { multiplier: 1.0, ..Verdict::default() };
+        for rule in self.rules.iter().filter(|r| r.matches(subject)) {
+            verdict.fired.push(rule.label.clone());
+            verdict.multiplier *= rule.multiplier;
+            verdict.offset += rule.offset;
+            verdict.mark = match (verdict.mark, rule.mark) {
+                (Some(Mark::Never), _) | (_, Some(Mark::Never)) => Some(Mark::Never),
+                (current, new) => new.or(current),
+            };
+        }
+        verdict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str) -> Rule {
+        Rule { name: Some(name.into()), ..Rule::default() }
+    }
+
+    #[test]
+    fn test_rules_combine() {
+        let rules = RuleSet::new(&[
+            Rule { comm: Some("chrome".into()), multiplier: Some(2.0), ..rule("browsers") },
+            Rule { cgroup: Some("/user.slice/*".into()), offset: Some(50.0), ..rule("users") },
+            Rule { comm: Some("postgres".into()), mark: Some(Mark::Never), ..rule("db") },
+            Rule { comm: Some("postgres".into()), mark: Some(Mark::Sacrificial), ..rule("oops") },
+        ])
+        .unwrap();
+
+        let cg = "/user.slice/user-1000.slice/session-2.scope";
+        let v = rules.evaluate(&Subject::new(1, "chrome", cg));
+        assert_eq!(v.fired, vec!["browsers", "users"]);
+        assert_eq!(v.adjust(100.0), 250.0);
+        assert_eq!(v.mark, None);
+
+        let v = rules.evaluate(&Subject::new(1, "chromium", "/system.slice/x.service"));
+        assert!(v.fired.is_empty());
+        assert_eq!(v.adjust(100.0), 100.0);
+
+        let v = rules.evaluate(&Subject::new(1, "postgres", "/system.slice/postgresql.service"));
+        assert_eq!(v.mark, Some(Mark::Never));
+    }
+
+    #[test]
+    fn test_rules_read_proc() {
+        let me = std::process::id() as i32;
+        let uid = nix::unistd::getuid().as_raw();
+        let rules = RuleSet::new(&[
+            Rule { user: Some(uid.to_string()), exe: Some("/**/*".into()), offset: Some(1.0), ..rule("mine") },
+            Rule { cmdline: Some("^no such program".into()), offset: Some(1.0), ..rule("other") },
+        ])
+        .unwrap();
+        let v = rules.evaluate(&Subject::new(me, "test", "/"));
+        assert_eq!(v.fired, vec!["mine"]);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        // No matcher
+        assert!(RuleSet::new(&[Rule { offset: Some(1.0), ..rule("a") }]).is_err());
+        // No effect
+        assert!(RuleSet::new(&[Rule { comm: Some("x".into()), ..rule("a") }]).is_err());
+        assert!(RuleSet::new(&[Rule { cmdline: Some("(".into()), offset: Some(1.0), ..rule("a") }]).is_err());
+        assert!(RuleSet::new(&[Rule { exe: Some("[".into()), offset: Some(1.0), ..rule("a") }]).is_err());
+        assert!(RuleSet::new(&[Rule { user: Some("no-such-user-here".into()), offset: Some(1.0), ..rule("a") }]).is_err());
+        // Non-finite effects
+        assert!(RuleSet::new(&[Rule { comm: Some("x".into()), offset: Some(f64::NAN), ..rule("a") }]).is_err());
+        assert!(RuleSet::new(&[Rule { comm: Some("x".into()), offset: Some(f64::INFINITY), ..rule("a") }]).is_err());
+    }
+}
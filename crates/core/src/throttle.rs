@@ -0,0 +1,100 @@
+use anyhow::{bail, Context, Result};
+use std::time::Instant;
+
+use crate::actions;
+use crate::cgroups::CgroupInfo;
+
+/* Soft-pressure throttling: lower memory.high on an offending cgroup so the
+ * kernel reclaims from it and slows its allocations, and remember the
+ * original value so it can be put back once pressure clears. */
+
+/* Never throttle a cgroup below this, whatever it currently uses */
+const MIN_MEMORY_HIGH: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Throttled {
+    pub cgroup: CgroupInfo,
+    pub original: String,
+    pub applied: u64,
+    pub usage: u64,
+    pub at: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct ThrottleSet {
+    throttled: Vec<Throttled>,
+}
+
+impl ThrottleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, cgroup_path: &str) -> bool {
+        self.throttled.iter().any(|t| t.cgroup.raw_path == cgroup_path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.throttled.is_empty()
+    }
+
+    pub fn cgroups(&self) -> Vec<String> {
+        self.throttled.iter().map(|t| t.cgroup.raw_path.clone()).collect()
+    }
+
+    /* Set memory.high to `pct` percent of the group's current usage */
+    pub fn throttle(&mut self, cgroup: &CgroupInfo, pct: u8) -> Result<Throttled> {
+        if self.contains(&cgroup.raw_path) {
+            bail!("{} is already throttled", cgroup.raw_path);
+        }
+        let original = cgroup.read_value("memory.high")?;
+        let usage: u64 = cgroup
+            .read_value("memory.current")?
+            .parse()
+            .context("parsing memory.current")?;
+
+        let applied = target_high(usage, &original, pct);
+        actions::write_memory_high(&cgroup.fs_path(), &applied.to_string())?;
+
+        let t = Throttled { cgroup: cgroup.clone(), original, applied, usage, at: Instant::now() };
+        self.throttled.push(t.clone());
+        Ok(t)
+    }
+
+    /* Put every original memory.high back. Cgroups that disappeared in the
+     * meantime are dropped silently. */
+    pub fn restore_all(&mut self) -> Vec<(Throttled, Result<()>)> {
+        self.throttled
+            .drain(..)
+            .filter(|t| t.cgroup.fs_path().exists())
+            .map(|t| {
+                let res = actions::write_memory_high(&t.cgroup.fs_path(), &t.original);
+                (t, res)
+            })
+            .collect()
+    }
+}
+
+fn target_high(usage: u64, original: &str, pct: u8) -> u64 {
+    let mut target = (usage.saturating_mul(pct as u64) / 100).max(MIN_MEMORY_HIGH);
+    // Never loosen an existing limit
+    if let Ok(limit) = original.parse::<u64>() {
+        target = target.min(limit);
+    }
+    target
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_high() {
+        let gib = 1024 * 1024 * 1024;
+        assert_eq!(target_high(4 * gib, "max", 75), 3 * gib);
+        // Floor for small groups
+        assert_eq!(target_high(10 * 1024 * 1024, "max", 50), MIN_MEMORY_HIGH);
+        // Existing tighter limit is kept
+        assert_eq!(target_high(4 * gib, &gib.to_string(), 90), gib);
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/* Short history of memory headroom (MemAvailable plus usable swap) used to
+ * predict when it runs out. The decline rate is the least-squares slope over
+ * the window, which smooths out single noisy samples and copes with the
+ * irregular spacing PSI-triggered wakeups produce. */
+
+/* Fewer samples than this give no prediction */
+const MIN_SAMPLES: usize = 3;
+
+/* Nor do samples covering less than this share of the window: a single step
+ * allocation over a few closely spaced wakeups looks like a steep decline */
+const MIN_SPAN_FRACTION: f64 = 0.5;
+
+#[derive(Debug)]
+pub struct Trend {
+    window: Duration,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl Trend {
+    pub fn new(window: Duration) -> Self {
+        Self { window, samples: VecDeque::new() }
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    pub fn push(&mut self, headroom_kb: u64) {
+        self.push_at(Instant::now(), headroom_kb);
+    }
+
+    pub fn push_at(&mut self, now: Instant, headroom_kb: u64) {
+        self.samples.push_back((now, headroom_kb as f64));
+        while let Some(&(t, _)) = self.samples.front() {
+            if now.saturating_duration_since(t) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /* Change of headroom in kB/s; negative while memory is being eaten */
+    pub fn rate_kb_per_sec(&self) -> Option<f64> {
+        if self.samples.len() < MIN_SAMPLES {
+            return None;
+        }
+        let t0 = self.samples.front()?.0;
+        let span = self.samples.back()?.0.duration_since(t0);
+        if span < self.window.mul_f64(MIN_SPAN_FRACTION) {
+            return None;
+        }
+        let n = self.samples.len() as f64;
+        let xs: Vec<f64> = self.samples.iter().map(|(t, _)| t.duration_since(t0).as_secs_f64()).collect();
+        let mean_x = xs.iter().sum::<f64>() / n;
+        let mean_y = self.samples.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+        let mut num = 0.0;
+        let mut den = 0.0;
+        for (x, (_, y)) in xs.iter().zip(self.samples.iter()) {
+            num += (x - mean_x) * (y - mean_y);
+            den += (x - mean_x) * (x - mean_x);
+        }
+        if den <= f64::EPSILON {
+            return None;
+        }
+        Some(num / den)
+    }
+
+    /* How long until headroom reaches zero at the current rate of decline.
+     * None while stable or growing. */
+    pub fn time_to_exhaustion(&self) -> Option<Duration> {
+        let rate = self.rate_kb_per_sec()?;
+        if rate >= 0.0 {
+            return None;
+        }
+        let (_, latest) = *self.samples.back()?;
+        Some(Duration::from_secs_f64((latest / -rate).max(0.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steady_decline() {
+        let mut trend = Trend::new(Duration::from_secs(8));
+        let t0 = Instant::now();
+        // 100 MB/s drain from 2 GB
+        for i in 0..5 {
+            trend.push_at(t0 + Duration::from_secs(i), 2_000_000 - i * 100_000);
+        }
+        let rate = trend.rate_kb_per_sec().unwrap();
+        assert!((rate + 100_000.0).abs() < 1e-6);
+        // 1.6 GB left at 100 MB/s
+        assert_eq!(trend.time_to_exhaustion().unwrap().as_secs(), 16);
+    }
+
+    #[test]
+    fn test_no_prediction() {
+        let mut trend = Trend::new(Duration::from_secs(6));
+        let t0 = Instant::now();
+        trend.push_at(t0, 1_000_000);
+        trend.push_at(t0 + Duration::from_secs(1), 900_000);
+        // Not enough samples yet
+        assert!(trend.rate_kb_per_sec().is_none());
+
+        // Growing headroom never predicts exhaustion
+        trend.push_at(t0 + Duration::from_secs(2), 1_200_000);
+        trend.push_at(t0 + Duration::from_secs(3), 1_400_000);
+        assert!(trend.rate_kb_per_sec().is_some());
+        assert!(trend.time_to_exhaustion().is_none());
+    }
+
+    #[test]
+    fn test_step_allocation() {
+        // One 2 GB allocation on a healthy 16 GB box, sampled 2s apart
+        let mut trend = Trend::new(Duration::from_secs(30));
+        let t0 = Instant::now();
+        for (i, gb) in [8, 6, 6].into_iter().enumerate() {
+            trend.push_at(t0 + Duration::from_secs(2 * i as u64), gb * 1_000_000);
+        }
+        // Too short a span to call it a trend
+        assert!(trend.time_to_exhaustion().is_none());
+
+        // Once headroom stays put the step flattens out well past the horizon
+        for i in 3..9 {
+            trend.push_at(t0 + Duration::from_secs(2 * i), 6_000_000);
+        }
+        assert!(trend.time_to_exhaustion().unwrap() > Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_old_samples_expire() {
+        let mut trend = Trend::new(Duration::from_secs(10));
+        let t0 = Instant::now();
+        for i in 0..5 {
+            trend.push_at(t0 + Duration::from_secs(i), 1_000_000 - i * 100_000);
+        }
+        // A long flat stretch pushes the drop out of the window
+        for i in 20..27 {
+            trend.push_at(t0 + Duration::from_secs(i), 600_000);
+        }
+        assert_eq!(trend.len(), 7);
+        assert_eq!(trend.rate_kb_per_sec(), Some(0.0));
+        assert!(trend.time_to_exhaustion().is_none());
+    }
+}
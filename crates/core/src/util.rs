@@ -0,0 +1,6 @@
+/* Small helpers shared by the /proc readers and the reserve */
+
+pub fn page_size() -> usize {
+    let sz = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if sz > 0 { sz as usize } else { 4096 }
+}
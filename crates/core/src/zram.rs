@@ -0,0 +1,268 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::mem::{self, MemInfo};
+
+/* Compressed swap: zram block devices and the zswap cache. Pages swapped to
+ * zram still live in RAM, only compressed, so SwapFree on a zram device
+ * promises headroom that has to come out of MemAvailable. Everything here is
+ * best effort - missing files just mean the feature isn't in use. */
+
+/* Ratio assumed for zram that hasn't stored anything yet */
+const DEFAULT_RATIO: f64 = 2.0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ZramDevice {
+    pub name: String,
+    pub disksize: u64,
+    /* Uncompressed bytes stored */
+    pub orig_data_size: u64,
+    pub compr_data_size: u64,
+    /* RAM really taken, including allocator overhead */
+    pub mem_used_total: u64,
+    /* 0 when unlimited */
+    pub mem_limit: u64,
+}
+
+impl ZramDevice {
+    pub fn ratio(&self) -> Option<f64> {
+        if self.orig_data_size == 0 || self.mem_used_total == 0 {
+            return None;
+        }
+        Some(self.orig_data_size as f64 / self.mem_used_total as f64)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ZswapStats {
+    pub enabled: bool,
+    pub max_pool_percent: Option<u8>,
+    pub compressor: Option<String>,
+    /* Compressed pool size in RAM */
+    pub pool_bytes: Option<u64>,
+    /* Uncompressed size of the pages held in the pool */
+    pub stored_bytes: Option<u64>,
+}
+
+impl ZswapStats {
+    pub fn ratio(&self) -> Option<f64> {
+        match (self.pool_bytes, self.stored_bytes) {
+            (Some(pool), Some(stored)) if pool > 0 && stored > 0 => Some(stored as f64 / pool as f64),
+            _ => None,
+        }
+    }
+}
+
+/* One line of /proc/swaps, sizes in kB */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapArea {
+    pub path: String,
+    pub size_kb: u64,
+    pub used_kb: u64,
+}
+
+impl SwapArea {
+    pub fn is_zram(&self) -> bool {
+        self.path.starts_with("/dev/zram")
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompressedSwap {
+    pub zram: Vec<ZramDevice>,
+    pub zswap: Option<ZswapStats>,
+    pub swaps: Vec<SwapArea>,
+}
+
+impl CompressedSwap {
+    pub fn sample() -> Self {
+        let swaps = fs::read_to_string("/proc/swaps").map(|s| parse_swaps(&s)).unwrap_or_default();
+        let meminfo = mem::sample().ok();
+        Self { zram: zram_devices(), zswap: zswap_stats(meminfo.as_ref()), swaps }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.zram.is_empty() && !self.zswap.as_ref().map(|z| z.enabled).unwrap_or(false)
+    }
+
+    /* RAM consumed by compressed swap: zram pools plus the zswap pool */
+    pub fn ram_used_bytes(&self) -> u64 {
+        let zram: u64 = self.zram.iter().map(|d| d.mem_used_total).sum();
+        let zswap = self.zswap.as_ref().and_then(|z| z.pool_bytes).unwrap_or(0);
+        zram + zswap
+    }
+
+    /* Effective compression ratio over every zram device */
+    pub fn zram_ratio(&self) -> Option<f64> {
+        let orig: u64 = self.zram.iter().map(|d| d.orig_data_size).sum();
+        let used: u64 = self.zram.iter().map(|d| d.mem_used_total).sum();
+        if orig == 0 || used == 0 {
+            return None;
+        }
+        Some(orig as f64 / used as f64)
+    }
+
+    /* SwapFree that can really be used: free space on zram swap only counts
+     * as far as MemAvailable (and the device's mem_limit) can hold it once
+     * compressed. Disk swap counts in full. */
+    pub fn effective_swap_free_kb(&self, m: &MemInfo) -> u64 {
+        let zram_free: u64 = self.swaps.iter().filter(|s| s.is_zram()).map(|s| s.size_kb.saturating_sub(s.used_kb)).sum();
+        if zram_free == 0 {
+            return m.swap_free;
+        }
+        let disk_free = m.swap_free.saturating_sub(zram_free);
+        let ratio = self.zram_ratio().unwrap_or(DEFAULT_RATIO);
+
+        let mut usable = zram_free.min((m.available() as f64 * ratio) as u64);
+        // Devices with a mem_limit can't grow past it whatever MemAvailable says
+        if !self.zram.is_empty() && self.zram.iter().all(|d| d.mem_limit > 0) {
+            let room: u64 = self.zram.iter().map(|d| d.mem_limit.saturating_sub(d.mem_used_total) / 1024).sum();
+            usable = usable.min((room as f64 * ratio) as u64);
+        }
+        disk_free + usable
+    }
+
+    pub fn effective_swap_free_pct(&self, m: &MemInfo) -> Option<f64> {
+        if m.swap_total == 0 {
+            return None;
+        }
+        Some((self.effective_swap_free_kb(m) as f64 / m.swap_total as f64) * 100.0)
+    }
+}
+
+fn zram_devices() -> Vec<ZramDevice> {
+    let Ok(entries) = fs::read_dir("/sys/block") else { return Vec::new() };
+    let mut devices: Vec<ZramDevice> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("zram"))
+        .filter_map(|e| {
+            let dir = e.path();
+            let mm_stat = fs::read_to_string(dir.join("mm_stat")).ok()?;
+            let mut dev = parse_mm_stat(&mm_stat)?;
+            dev.name = e.file_name().to_string_lossy().into_owned();
+            dev.disksize = read_u64(&dir.join("disksize")).unwrap_or(0);
+            // Loaded module, device never set up
+            (dev.disksize > 0).then_some(dev)
+        })
+        .collect();
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    devices
+}
+
+fn zswap_stats(meminfo: Option<&MemInfo>) -> Option<ZswapStats> {
+    let params = Path::new("/sys/module/zswap/parameters");
+    let enabled = fs::read_to_string(params.join("enabled")).ok()?;
+    let mut stats = ZswapStats {
+        enabled: enabled.trim() == "Y",
+        max_pool_percent: fs::read_to_string(params.join("max_pool_percent")).ok().and_then(|s| s.trim().parse().ok()),
+        compressor: fs::read_to_string(params.join("compressor")).ok().map(|s| s.trim().to_string()),
+        ..Default::default()
+    };
+
+    // Kernels since 5.19 report the pool in meminfo; older ones only in debugfs
+    stats.pool_bytes = meminfo.and_then(|m| m.zswap).map(|kb| kb * 1024);
+    stats.stored_bytes = meminfo.and_then(|m| m.zswapped).map(|kb| kb * 1024);
+    if stats.pool_bytes.is_none() {
+        let debug = Path::new("/sys/kernel/debug/zswap");
+        stats.pool_bytes = read_u64(&debug.join("pool_total_size"));
+        stats.stored_bytes = read_u64(&debug.join("stored_pages")).map(|p| p * crate::util::page_size() as u64);
+    }
+    Some(stats)
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/* orig_data_size compr_data_size mem_used_total mem_limit mem_used_max ... */
+fn parse_mm_stat(content: &str) -> Option<ZramDevice> {
+    let fields: Vec<u64> = content.split_whitespace().map(|v| v.parse().ok()).collect::<Option<_>>()?;
+    if fields.len() < 4 {
+        return None;
+    }
+    Some(ZramDevice {
+        orig_data_size: fields[0],
+        compr_data_size: fields[1],
+        mem_used_total: fields[2],
+        mem_limit: fields[3],
+        ..Default::default()
+    })
+}
+
+fn parse_swaps(content: &str) -> Vec<SwapArea> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split_whitespace().collect();
+            if f.len() < 4 {
+                return None;
+            }
+            Some(SwapArea { path: f[0].to_string(), size_kb: f[2].parse().ok()?, used_kb: f[3].parse().ok()? })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mem(mem_available: u64, swap_total: u64, swap_free: u64) -> MemInfo {
+        MemInfo {
+            mem_total: 8_000_000,
+            mem_available: Some(mem_available),
+            swap_total,
+            swap_free,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_mm_stat_and_swaps() {
+        let dev = parse_mm_stat("1073741824 268435456 300000000 0 310000000 1200 0 0 0\n").unwrap();
+        assert_eq!(dev.orig_data_size, 1 << 30);
+        assert_eq!(dev.mem_limit, 0);
+        assert!((dev.ratio().unwrap() - 3.579).abs() < 0.01);
+        assert!(parse_mm_stat("1 2\n").is_none());
+
+        let swaps = parse_swaps(
+            "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n\
+             /dev/zram0                              partition\t4000000\t\t1000000\t\t100\n\
+             /swapfile                               file\t\t2000000\t\t0\t\t-2\n",
+        );
+        assert_eq!(swaps.len(), 2);
+        assert!(swaps[0].is_zram());
+        assert!(!swaps[1].is_zram());
+        assert_eq!(swaps[0].used_kb, 1_000_000);
+    }
+
+    #[test]
+    fn test_effective_swap_free() {
+        let cs = CompressedSwap {
+            zram: vec![ZramDevice {
+                name: "zram0".into(),
+                orig_data_size: 3 << 30,
+                mem_used_total: 1 << 30,
+                ..Default::default()
+            }],
+            zswap: None,
+            swaps: vec![
+                SwapArea { path: "/dev/zram0".into(), size_kb: 8_000_000, used_kb: 3_000_000 },
+                SwapArea { path: "/swapfile".into(), size_kb: 2_000_000, used_kb: 0 },
+            ],
+        };
+        // 5 GB free on zram but only 1 GB of RAM to hold it at 3:1
+        let m = mem(1_000_000, 10_000_000, 7_000_000);
+        assert_eq!(cs.effective_swap_free_kb(&m), 2_000_000 + 3_000_000);
+        assert_eq!(cs.effective_swap_free_pct(&m), Some(50.0));
+
+        // Plenty of RAM: SwapFree is accurate
+        let m = mem(4_000_000, 10_000_000, 7_000_000);
+        assert_eq!(cs.effective_swap_free_kb(&m), 7_000_000);
+
+        // Without zram swap nothing changes
+        let disk_only = CompressedSwap::default();
+        assert_eq!(disk_only.effective_swap_free_kb(&m), 7_000_000);
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::{bail, Context, Result};
+use std::io::BufReader;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+use std::fs;
+use tracing::warn;
+
+use sentinel_core::control::{self, Request, RequestEnvelope, Response, ResponseEnvelope, PROTOCOL_VERSION};
+
+use crate::Event;
+
+/* A request forwarded from the socket thread to the main loop, which owns
+ * the reserve and the config and answers through `reply`. */
+pub struct ControlMsg {
+    pub request: Request,
+    pub reply: Sender<Response>,
+}
+
+/* Requests are one short line, so a client that hasn't sent it by now never will */
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/* Each connection gets its own thread so a stalled client can't hold up
+ * anyone else; this bounds how many a local user can tie up at once */
+const MAX_CONNECTIONS: usize = 16;
+
+pub fn spawn_listener(path: &Path, tx: Sender<Event>) -> Result<()> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("another sentinel is already listening on {}", path.display());
+        }
+        // Stale socket from a previous run
+        fs::remove_file(path).with_context(|| format!("removing stale {}", path.display()))?;
+    }
+
+    let listener = UnixListener::bind(path).with_context(|| format!("binding {}", path.display()))?;
+    // Anyone may read status; mutating requests are checked against the peer uid
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+
+    std::thread::spawn(move || {
+        let active = Arc::new(AtomicUsize::new(0));
+        for stream in listener.incoming() {
+            let s = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!(error = %e, "control socket accept failed");
+                    continue;
+                }
+            };
+            if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                active.fetch_sub(1, Ordering::SeqCst);
+                warn!("too many control connections; dropping one");
+                continue;
+            }
+            let (tx, active) = (tx.clone(), Arc::clone(&active));
+            std::thread::spawn(move || {
+                if let Err(e) = serve(s, &tx) {
+                    warn!(error = %e, "control request failed");
+                }
+                active.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn serve(stream: UnixStream, tx: &Sender<Event>) -> Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let response = match control::read_message::<RequestEnvelope>(&mut BufReader::new(&stream)) {
+        Err(e) => Response::Error { message: format!("{:#}", e) },
+        Ok(env) if env.version != PROTOCOL_VERSION => Response::Error {
+            message: format!(
+                "unsupported control protocol v{} (daemon speaks v{})",
+                env.version, PROTOCOL_VERSION
+            ),
+        },
+        Ok(env) if env.request.is_mutating() && peer_uid(&stream) != Some(0) => Response::Error {
+            message: "permission denied: this request requires root".into(),
+        },
+        Ok(env) => {
+            let (reply_tx, reply_rx) = mpsc::channel();
+            tx.send(Event::Control(ControlMsg { request: env.request, reply: reply_tx }))
+                .context("main loop is gone")?;
+            reply_rx.recv_timeout(REPLY_TIMEOUT).unwrap_or(Response::Error {
+                message: "daemon did not answer in time".into(),
+            })
+        }
+    };
+
+    control::write_message(&mut &stream, &ResponseEnvelope { version: PROTOCOL_VERSION, response })
+}
+
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rc == 0 { Some(cred.uid) } else { None }
+}
@@ -1,20 +1,37 @@
-use anyhow::Result;
-use std::{path::PathBuf, sync::{Arc, atomic::{AtomicBool, Ordering}}, time::Duration, fs, process};
+use anyhow::{Context, Result};
+use std::{path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, RecvTimeoutError}}, time::{Duration, Instant}, fs, process};
 use signal_hook::consts::signal::*;
 use signal_hook::iterator::Signals;
-use tracing::{info, warn, error, Level};
+use tracing::{debug, info, warn, error, Level};
 use clap::Parser;
 
 use sentinel_core::{
     config::Config, 
+    control::{self as ctl, DaemonStatus, Decision, Request, Response},
     mem, 
-    policy::{self, PressureState}, 
-    reserve,
+    policy::{self, Dwell, PressureMachine, PressureState, SwapLevel, Transition}, 
+    reserve::Reserve,
     psi::PSIMetrics,
-    procinfo,
+    procinfo::{self, ProcWithBadness, Scoring},
+    proctree::ProcTree,
     actions,
+    cgroups::{self, CgroupInfo, CgroupSlice, ProtectedUnits, SliceClasses},
+    exclude::Exclusions,
+    rules::RuleSet,
+    freeze::FrozenSet,
+    ladder::{self, Ladder, LadderAction, Rung},
+    ratelimit::{ActionLimiter, Permit},
+    reclaim,
+    throttle::ThrottleSet,
+    trend::Trend,
+    zram::CompressedSwap,
 };
 
+mod control;
+mod trigger;
+
+use control::ControlMsg;
+
 const PID_FILE: &str = "/var/run/sentinel.pid";
 
 #[derive(Parser, Debug)]
@@ -27,6 +44,724 @@ struct Args {
     stop: bool,
 }
 
+/* Anything that wakes the main loop before the next scan */
+pub enum Event {
+    Control(ControlMsg),
+    PsiTrigger,
+}
+
+/* State shared between the decision loop and control requests */
+struct Runtime {
+    cfg: Config,
+    cfg_path: PathBuf,
+    started: Instant,
+    machine: PressureMachine,
+    avail_pct: f64,
+    psi_some_avg10: Option<f64>,
+    psi_full_avg10: Option<f64>,
+    swap: Option<SwapLevel>,
+    trend: Trend,
+    /* Set while the trend predicts exhaustion within the soft horizon */
+    exhaustion: Option<Duration>,
+    last_transition: Option<Transition>,
+    last_decision: Option<Decision>,
+    reserve: Reserve,
+    last_refill: Option<Instant>,
+    frozen: FrozenSet,
+    throttle: ThrottleSet,
+    limiter: ActionLimiter,
+    ladder: Ladder,
+    /* `cfg.exclude_names`, compiled */
+    exclude: Exclusions,
+    /* `cfg.rules`, compiled */
+    rules: RuleSet,
+    /* `cfg.protected_units`, compiled */
+    protected: ProtectedUnits,
+    /* `cfg.slice_classes`, compiled */
+    classes: SliceClasses,
+}
+
+impl Runtime {
+    fn reload(&mut self) -> Result<()> {
+        let cfg = Config::load_from(&self.cfg_path)?;
+        let exclude = Exclusions::new(&cfg.exclude_names)?;
+        let rules = RuleSet::new(&cfg.rules)?;
+        let protected = ProtectedUnits::new(&cfg.protected_units)?;
+        let classes = SliceClasses::new(&cfg.slice_classes)?;
+        self.cfg = cfg;
+        self.exclude = exclude;
+        self.rules = rules;
+        self.protected = protected;
+        self.classes = classes;
+        self.limiter.set_limits(self.cfg.max_actions_per_min, self.cfg.emergency_max_actions_per_min);
+        self.trend.set_window(Duration::from_secs(self.cfg.trend_window_sec));
+        self.machine.set_dwell(dwell(&self.cfg));
+        self.ladder.set_rungs(ladder::rungs_for(&self.cfg));
+        warn_config(&self.cfg);
+        if self.reserve.set_layout(self.cfg.reserve_mb, self.cfg.reserve_chunks, self.cfg.reserve_mlock) {
+            // Refilled chunk by chunk once pressure allows
+            info!(size_mb = self.cfg.reserve_mb, chunks = self.cfg.reserve_chunks, "reserve layout changed; released");
+        }
+        info!("reloaded config");
+        Ok(())
+    }
+
+    fn scoring(&self, total_mem: u64) -> Scoring<'_> {
+        Scoring::new(&self.cfg, &self.rules, &self.classes, total_mem)
+    }
+
+    /* Classify one sample. With `exit` the thresholds are moved by the
+     * hysteresis margins, giving the level needed to step down. */
+    fn classify(&self, m: &mem::MemInfo, psi: Option<&PSIMetrics>, swap_free_pct: Option<f64>, exit: bool) -> PressureState {
+        let (mem_margin, psi_margin) = if exit { (self.cfg.hysteresis_pct, self.cfg.hysteresis_psi_pct) } else { (0, 0.0) };
+        let swap = swap_free_pct.map(|free_pct| SwapLevel {
+            free_pct,
+            soft: self.cfg.swap_soft_threshold_pct.saturating_add(mem_margin),
+            hard: self.cfg.swap_hard_threshold_pct.saturating_add(mem_margin),
+            hard_needs_mem: self.cfg.swap_hard_needs_mem_pressure,
+        });
+        policy::classify_with_psi(
+            m.avail_pct(),
+            self.cfg.soft_threshold_pct.saturating_add(mem_margin),
+            self.cfg.hard_threshold_pct.saturating_add(mem_margin),
+            psi,
+            self.cfg.psi_soft_pct - psi_margin,
+            self.cfg.psi_hard_pct - psi_margin,
+            swap,
+        )
+    }
+
+    fn record(&mut self, decision: Decision) {
+        self.last_decision = Some(decision);
+    }
+
+    fn status(&mut self) -> DaemonStatus {
+        DaemonStatus {
+            pid: process::id(),
+            version: sentinel_core::VERSION.to_string(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            mode: self.cfg.mode.clone(),
+            state: self.machine.state(),
+            state_since_secs: self.machine.since().elapsed().as_secs(),
+            avail_pct: self.avail_pct,
+            psi_some_avg10: self.psi_some_avg10,
+            swap_free_pct: self.swap.map(|s| s.free_pct),
+            headroom_rate_kb_s: self.trend.rate_kb_per_sec(),
+            exhaustion_secs: self.trend.time_to_exhaustion().map(|t| t.as_secs()),
+            reserve_held: self.reserve.is_held(),
+            reserve_mb: self.cfg.reserve_mb,
+            reserve_resident_bytes: self.reserve.resident_bytes(),
+            reserve_locked: self.reserve.is_locked(),
+            reserve_chunks_held: self.reserve.held_chunks(),
+            reserve_chunks_total: self.reserve.total_chunks(),
+            last_decision: self.last_decision.clone(),
+            last_transition: self.last_transition.clone(),
+            ladder_rung: self.ladder.current().map(|r| r.action.to_string()),
+            ladder_position: self.ladder.position().map(|i| i + 1),
+            ladder_len: self.ladder.rungs().len(),
+            ladder_rung_secs: self.ladder.held().map(|d| d.as_secs()),
+            frozen_pids: self.frozen.pids(),
+            frozen_cgroups: self.frozen.cgroups(),
+            throttled_cgroups: self.throttle.cgroups(),
+            actions_remaining: self.limiter.remaining(),
+            emergency_actions_remaining: self.limiter.emergency_remaining(),
+        }
+    }
+
+    /* Carry out one ladder rung. Watch mode only ever releases the reserve. */
+    fn run_rung(&mut self, rung: &Rung, state: PressureState, total_mem: u64, reason: &str) {
+        if self.cfg.mode == "watch" && rung.action.acts_on_processes() {
+            info!(action = %rung.action, "watch mode; not acting");
+            return;
+        }
+        match rung.action {
+            // Deflated every tick once reached
+            LadderAction::ReleaseReserve => {}
+            LadderAction::Reclaim => self.reclaim(state),
+            LadderAction::Throttle => self.throttle_top(state, total_mem),
+            LadderAction::Freeze => {
+                if let Some(victim) = self.pick_victim(total_mem) {
+                    self.freeze(state, &victim, reason.to_string());
+                }
+            }
+            LadderAction::Term | LadderAction::Kill => self.terminate(state, rung.action, total_mem, reason),
+        }
+    }
+
+    /* Top candidate that isn't already frozen - a frozen process still holds
+     * its memory, so freezing it again gains nothing */
+    fn pick_victim(&self, total_mem: u64) -> Option<ProcWithBadness> {
+        let scoring = self.scoring(total_mem);
+        let mut procs = match procinfo::processes_with_badness(
+            &self.exclude,
+            &self.protected,
+            &scoring,
+        ) {
+            Ok(procs) => procs,
+            Err(e) => {
+                error!(error = %e, "failed to enumerate processes");
+                return None;
+            }
+        };
+        if self.cfg.subtree_badness {
+            match ProcTree::build() {
+                Ok(tree) => {
+                    procs = procinfo::group_by_subtree(
+                        procs,
+                        &tree,
+                        &self.exclude,
+                        &self.protected,
+                        &scoring,
+                    );
+                }
+                Err(e) => warn!(error = %e, "failed to build process tree; ranking single processes"),
+            }
+        }
+        let victim = procs.into_iter().find(|p| !self.frozen.covers(p.pid, &p.cgroup_path))?;
+        info!(
+            pid = victim.pid,
+            name = %victim.name,
+            rss_mb = victim.rss_bytes / (1024*1024),
+            pss_mb = ?victim.mem.pss.map(|b| b / (1024*1024)),
+            oom_score = ?victim.oom_score,
+            rules = ?victim.rules_fired,
+            freed_mb = victim.freed_bytes() / (1024*1024),
+            subtree_procs = ?victim.subtree.as_ref().map(|t| t.procs),
+            badness = %victim.badness_score,
+            slice = %victim.class_name(),
+            unit = ?victim.cgroup_unit,
+            cgroup_psi_some = ?victim.cgroup_psi_some,
+            "selected target for action"
+        );
+        Some(victim)
+    }
+
+    /* SIGTERM or kill. A process we froze earlier goes first: it was the
+     * worst offender and freezing it didn't relieve pressure. */
+    fn terminate(&mut self, state: PressureState, action: LadderAction, total_mem: u64, reason: &str) {
+        if let Some(f) = self.frozen.escalation_candidate().cloned() {
+            let frozen_secs = f.frozen_at.elapsed().as_secs();
+            warn!(pid = f.pid, name = %f.name, frozen_secs, action = %action,
+                  "pressure persists after freeze; escalating");
+            let reason = format!("{}; still {:?} {}s after freeze", reason, state, frozen_secs);
+            let done = match action {
+                LadderAction::Term => self.term(state, f.pid, &f.name, f.start_time, reason),
+                _ => self.kill(state, f.pid, &f.name, f.start_time, reason),
+            };
+            // A stopped process can't act on SIGTERM; thaw it either way
+            if done {
+                if let Some((_, Err(e))) = self.frozen.release(f.pid) {
+                    error!(error = %e, "failed to thaw cgroup after signalling");
+                }
+                if action == LadderAction::Term {
+                    let _ = actions::sigcont(f.pid);
+                }
+            }
+            return;
+        }
+
+        let Some(victim) = self.pick_victim(total_mem) else { return };
+        match action {
+            LadderAction::Term => self.term(state, victim.pid, &victim.name, victim.start_time, reason.to_string()),
+            _ => self.kill(state, victim.pid, &victim.name, victim.start_time, reason.to_string()),
+        };
+    }
+
+    /* SIGTERM only, giving the victim a chance to exit cleanly */
+    fn term(&mut self, state: PressureState, pid: i32, name: &str, start_time: u64, reason: String) -> bool {
+        if !self.permit(state, pid, name, "term") {
+            return false;
+        }
+        match actions::check_identity(pid, start_time).and_then(|_| actions::sigterm(pid)) {
+            Ok(()) => {
+                info!(pid, name, "sent SIGTERM");
+                self.record(Decision::new(state, "term", reason).with_target(pid, name));
+                true
+            }
+            Err(e) => {
+                error!(pid, error = %e, "failed to send SIGTERM");
+                self.record(Decision::new(state, "term_failed", format!("{}; {}", reason, e)).with_target(pid, name));
+                false
+            }
+        }
+    }
+
+    /* Every process action must take a slot from the rate limiter */
+    fn permit(&mut self, state: PressureState, pid: i32, name: &str, action: &str) -> bool {
+        let stalled = self
+            .psi_full_avg10
+            .map(|full| full >= self.cfg.emergency_psi_full_pct)
+            .unwrap_or(false);
+        match self.limiter.try_acquire(stalled) {
+            Some(Permit::Normal) => true,
+            Some(Permit::Emergency) => {
+                warn!(
+                    pid,
+                    action,
+                    psi_full_avg10 = ?self.psi_full_avg10,
+                    emergency_remaining = self.limiter.emergency_remaining(),
+                    "action budget exhausted; system stalling, using emergency override"
+                );
+                true
+            }
+            None => {
+                warn!(
+                    pid,
+                    name,
+                    action,
+                    max_actions_per_min = self.cfg.max_actions_per_min,
+                    "rate limit reached; refusing action"
+                );
+                self.record(Decision::new(
+                    state,
+                    "rate_limited",
+                    format!("{} refused: max_actions_per_min ({}) spent", action, self.cfg.max_actions_per_min),
+                ).with_target(pid, name));
+                false
+            }
+        }
+    }
+
+    /* Kill at the configured granularity; returns true if the victim is gone */
+    fn kill(&mut self, state: PressureState, pid: i32, name: &str, start_time: u64, reason: String) -> bool {
+        if !self.permit(state, pid, name, "kill") {
+            return false;
+        }
+
+        let (action, result) = match self.cfg.kill_granularity.as_str() {
+            "cgroup" => {
+                // The cgroup is looked up by PID, so it must still be the victim's
+                let cg = actions::check_identity(pid, start_time)
+                    .and_then(|_| CgroupInfo::for_pid(pid as u32))
+                    .ok()
+                    .and_then(|cg| self.whole_cgroup(pid, cg, "kill"));
+                match cg {
+                    Some(cg) if cg.fs_path().join("cgroup.procs").exists() => {
+                        let path = cg.fs_path();
+                        info!(pid, cgroup = %cg.raw_path, cgroup_kill = actions::cgroup_kill_supported(&path),
+                              "killing whole cgroup");
+                        ("kill_cgroup", actions::kill_cgroup(&path).map(|_| Some(cg.raw_path)))
+                    }
+                    _ => ("kill", actions::kill_process(pid, start_time).map(|_| None)),
+                }
+            }
+            "process-tree" => ("kill_tree", self.kill_tree(pid, start_time).map(|_| None)),
+            _ => ("kill", actions::kill_process(pid, start_time).map(|_| None)),
+        };
+
+        match result {
+            Err(e) => {
+                error!(error = %e, "failed to kill process");
+                self.record(Decision::new(state, "kill_failed", format!("{}; {}", reason, e)).with_target(pid, name));
+                false
+            }
+            Ok(cgroup) => {
+                info!(pid, action, "killed process");
+                let reason = match cgroup {
+                    Some(path) => format!("{}; cgroup {}", reason, path),
+                    None => reason,
+                };
+                self.record(Decision::new(state, action, reason).with_target(pid, name));
+                true
+            }
+        }
+    }
+
+    /* The victim and the descendants that belong with it; see
+     * `procinfo::joins_subtree` for who is spared */
+    fn kill_tree(&self, pid: i32, start_time: u64) -> Result<()> {
+        let head = CgroupInfo::for_pid(pid as u32)?;
+        actions::kill_process_tree(pid, start_time, |node| {
+            let joins = CgroupInfo::for_pid(node.pid as u32)
+                .map(|cg| procinfo::joins_subtree(node, &cg.raw_path, &head.raw_path, &self.exclude, &self.rules))
+                .unwrap_or(false);
+            if !joins {
+                info!(pid = node.pid, name = %node.name, "sparing descendant of victim");
+            }
+            joins
+        })
+    }
+
+    fn freeze(&mut self, state: PressureState, victim: &ProcWithBadness, reason: String) {
+        if victim.cgroup_slice == CgroupSlice::Init {
+            warn!(pid = victim.pid, name = %victim.name, "refusing to freeze a process in init.scope");
+            return;
+        }
+        if !self.permit(state, victim.pid, &victim.name, "freeze") {
+            return;
+        }
+
+        let result = match self.whole_cgroup_target(victim) {
+            Some(cg) => self
+                .frozen
+                .freeze_cgroup(victim.pid, &victim.name, victim.rss_bytes, &cg)
+                .map(|_| Some(cg.raw_path)),
+            None => self.frozen.freeze(victim.pid, &victim.name, victim.rss_bytes).map(|_| None),
+        };
+
+        match result {
+            Err(e) => {
+                error!(error = %e, "failed to freeze process");
+                self.record(Decision::new(state, "freeze_failed", format!("{}; {}", reason, e))
+                    .with_target(victim.pid, &victim.name));
+            }
+            Ok(Some(path)) => {
+                info!(pid = victim.pid, cgroup = %path, frozen = self.frozen.len(), "froze cgroup (cgroup.freeze)");
+                self.record(Decision::new(state, "freeze_cgroup", format!("{}; cgroup {}", reason, path))
+                    .with_target(victim.pid, &victim.name));
+            }
+            Ok(None) => {
+                info!(pid = victim.pid, frozen = self.frozen.len(), "froze process (SIGSTOP)");
+                self.record(Decision::new(state, "freeze", reason).with_target(victim.pid, &victim.name));
+            }
+        }
+    }
+
+    /* The victim's cgroup, if it should be frozen as a whole */
+    fn whole_cgroup_target(&self, victim: &ProcWithBadness) -> Option<CgroupInfo> {
+        if self.cfg.freeze_granularity != "cgroup" {
+            return None;
+        }
+        let cg = self.whole_cgroup(victim.pid, victim.cgroup(), "freeze")?;
+        if !actions::cgroup_freeze_supported(&cg.fs_path()) {
+            info!(pid = victim.pid, cgroup = %cg.raw_path, "cgroup.freeze unavailable; freezing process only");
+            return None;
+        }
+        Some(cg)
+    }
+
+    /* `cg` unless it must not be acted on as a whole */
+    fn whole_cgroup(&self, pid: i32, cg: CgroupInfo, action: &str) -> Option<CgroupInfo> {
+        if let Some(why) = cg.whole_cgroup_veto(&self.protected, cgroups::own_cgroup().as_deref()) {
+            info!(pid, action, reason = %why, "not acting on whole cgroup; targeting process only");
+            return None;
+        }
+        Some(cg)
+    }
+
+    /* Ask low-priority cgroups to give memory back until avail% reaches the
+     * level that ends soft pressure. Under hard pressure the kernel is
+     * reclaiming flat out already, so this is skipped. */
+    fn reclaim(&mut self, state: PressureState) {
+        if state != PressureState::Soft {
+            debug!(state = ?state, "not soft pressure; skipping proactive reclaim");
+            return;
+        }
+        let m = match mem::sample() {
+            Ok(m) => m,
+            Err(e) => {
+                error!(error = %e, "failed to sample memory");
+                return;
+            }
+        };
+        let goal_pct = self.cfg.soft_threshold_pct.saturating_add(self.cfg.hysteresis_pct);
+        let goal_kb = m.mem_total * goal_pct as u64 / 100;
+        if m.available() >= goal_kb {
+            return;
+        }
+
+        let procs = match procinfo::processes_with_badness(
+            &self.exclude,
+            &self.protected,
+            &self.scoring(m.mem_total * 1024),
+        ) {
+            Ok(procs) => procs,
+            Err(e) => {
+                error!(error = %e, "failed to enumerate processes");
+                return;
+            }
+        };
+        let targets = reclaim::targets(&procs, &self.cfg.reclaim_units, &self.protected);
+        if targets.is_empty() {
+            debug!("no cgroup eligible for memory.reclaim");
+            return;
+        }
+
+        let mut avail_kb = m.available();
+        let mut requested = 0;
+        let mut reclaimed = 0;
+        let mut groups = 0;
+        for cg in targets {
+            let needed = goal_kb.saturating_sub(avail_kb) * 1024;
+            match reclaim::reclaim(&cg, needed, self.cfg.reclaim_step_pct) {
+                Ok(r) if r.requested > 0 => {
+                    info!(
+                        cgroup = %cg.raw_path,
+                        requested_mb = r.requested / (1024 * 1024),
+                        reclaimed_mb = r.reclaimed / (1024 * 1024),
+                        complete = r.complete,
+                        "memory.reclaim"
+                    );
+                    requested += r.requested;
+                    reclaimed += r.reclaimed;
+                    groups += 1;
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!(cgroup = %cg.raw_path, error = %e, "memory.reclaim failed");
+                    continue;
+                }
+            }
+            match mem::sample() {
+                Ok(now) => avail_kb = now.available(),
+                Err(_) => break,
+            }
+            if avail_kb >= goal_kb {
+                break;
+            }
+        }
+        if groups == 0 {
+            return;
+        }
+
+        let after_pct = avail_kb as f64 / m.mem_total as f64 * 100.0;
+        info!(
+            cgroups = groups,
+            requested_mb = requested / (1024 * 1024),
+            reclaimed_mb = reclaimed / (1024 * 1024),
+            avail_before = %format!("{:.1}", m.avail_pct()),
+            avail_after = %format!("{:.1}", after_pct),
+            goal_met = avail_kb >= goal_kb,
+            "proactive reclaim done"
+        );
+        self.record(Decision::new(
+            state,
+            "reclaim",
+            format!(
+                "memory.reclaim freed {} MiB of {} MiB requested from {} cgroups; avail {:.1}% -> {:.1}% (goal {}%)",
+                reclaimed / (1024 * 1024),
+                requested / (1024 * 1024),
+                groups,
+                m.avail_pct(),
+                after_pct,
+                goal_pct
+            ),
+        ));
+    }
+
+    /* Lower memory.high on the worst cgroup that isn't protected or frozen.
+     * Once per pressure episode: the throttle rung repeats every tick, and
+     * the limits are only put back at healthy. Writing memory.high is
+     * reversible, so it doesn't spend the freeze/kill budget. */
+    fn throttle_top(&mut self, state: PressureState, total_mem: u64) {
+        if !self.throttle.is_empty() {
+            return;
+        }
+        let procs = match procinfo::processes_with_badness(
+            &self.exclude,
+            &self.protected,
+            &self.scoring(total_mem),
+        ) {
+            Ok(procs) => procs,
+            Err(e) => {
+                error!(error = %e, "failed to enumerate processes");
+                return;
+            }
+        };
+        let own = cgroups::own_cgroup();
+        let target = procs.iter().find_map(|p| {
+            if self.throttle.contains(&p.cgroup_path) || self.frozen.covers(p.pid, &p.cgroup_path) {
+                return None;
+            }
+            let cg = p.cgroup();
+            if cg.whole_cgroup_veto(&self.protected, own.as_deref()).is_some() || !cg.fs_path().join("memory.high").exists() {
+                return None;
+            }
+            Some((p, cg))
+        });
+        let Some((victim, cg)) = target else { return };
+
+        match self.throttle.throttle(&cg, self.cfg.soft_throttle_pct) {
+            Ok(t) => {
+                info!(
+                    cgroup = %t.cgroup.raw_path,
+                    before = %t.original,
+                    after = t.applied,
+                    usage = t.usage,
+                    "soft pressure: lowered memory.high"
+                );
+                self.record(Decision::new(
+                    state,
+                    "throttle",
+                    format!("memory.high {} -> {} on {}", t.original, t.applied, t.cgroup.raw_path),
+                ).with_target(victim.pid, &victim.name));
+            }
+            Err(e) => warn!(cgroup = %cg.raw_path, error = %e, "failed to lower memory.high"),
+        }
+    }
+
+    fn restore_throttles(&mut self) {
+        for (t, res) in self.throttle.restore_all() {
+            match res {
+                Ok(()) => info!(cgroup = %t.cgroup.raw_path, before = t.applied, after = %t.original, "restored memory.high"),
+                Err(e) => error!(cgroup = %t.cgroup.raw_path, error = %e, "failed to restore memory.high"),
+            }
+        }
+    }
+
+    /* Resume one frozen process per tick so a thundering herd of
+     * allocations doesn't push us straight back into pressure */
+    fn thaw_one(&mut self, state: PressureState) {
+        if let Some((f, res)) = self.frozen.thaw_next() {
+            match res {
+                Ok(()) => {
+                    info!(pid = f.pid, name = %f.name, remaining = self.frozen.len(), "thawed process (SIGCONT)");
+                    self.record(Decision::new(state, "thaw", "pressure cleared").with_target(f.pid, &f.name));
+                }
+                Err(e) => error!(pid = f.pid, error = %e, "failed to thaw process"),
+            }
+        }
+    }
+
+    fn handle(&mut self, req: Request) -> Response {
+        match req {
+            Request::Status => Response::Status(Box::new(self.status())),
+            Request::ReserveHold => {
+                if !self.reserve.is_held() {
+                    self.hold_reserve("control request");
+                }
+                self.reserve_response()
+            }
+            Request::ReserveRelease => {
+                if self.reserve.is_held() {
+                    self.reserve.release();
+                    info!("reserve released (control request)");
+                }
+                self.reserve_response()
+            }
+            Request::ReserveRebuild => {
+                self.reserve.release();
+                self.hold_reserve("control request (rebuild)");
+                self.reserve_response()
+            }
+            Request::Reload => match self.reload() {
+                Ok(()) => Response::Reloaded { path: Some(self.cfg_path.display().to_string()) },
+                Err(e) => {
+                    warn!(error = format!("{:#}", e), "failed to reload config; keeping previous");
+                    Response::Error { message: format!("reload failed: {:#}", e) }
+                }
+            },
+            Request::Config => Response::Config(Box::new(self.cfg.clone())),
+            Request::LastDecision => Response::Decision { decision: self.last_decision.clone() },
+        }
+    }
+
+    fn reserve_response(&self) -> Response {
+        Response::Reserve {
+            held: self.reserve.is_held(),
+            size_mb: self.cfg.reserve_mb,
+            resident_bytes: self.reserve.resident_bytes(),
+            chunks_held: self.reserve.held_chunks(),
+            chunks_total: self.reserve.total_chunks(),
+        }
+    }
+
+    /* Inflate the whole balloon and log what is really resident */
+    fn hold_reserve(&mut self, trigger: &str) -> bool {
+        self.reserve.set_layout(self.cfg.reserve_mb, self.cfg.reserve_chunks, self.cfg.reserve_mlock);
+        match self.reserve.hold() {
+            Ok(()) => {
+                info!(
+                    size_mb = self.cfg.reserve_mb,
+                    resident_mb = self.reserve.resident_bytes() / (1024 * 1024),
+                    locked = self.reserve.is_locked(),
+                    trigger,
+                    "reserve held"
+                );
+                if self.cfg.reserve_mlock && !self.reserve.is_locked() {
+                    warn!("could not mlock reserve (needs CAP_IPC_LOCK or a larger RLIMIT_MEMLOCK); it may be swapped out");
+                }
+                true
+            }
+            Err(e) => {
+                error!(error = %e, trigger, "failed to hold reserve");
+                false
+            }
+        }
+    }
+
+    /* Give back reserve chunks in proportion to how deep the pressure is */
+    fn deflate_reserve(&mut self, state: PressureState, m: &mem::MemInfo, psi: Option<&PSIMetrics>) {
+        let depth = policy::pressure_depth(
+            m.avail_pct(),
+            self.cfg.soft_threshold_pct,
+            self.cfg.hard_threshold_pct,
+            psi,
+            self.cfg.psi_soft_pct,
+            self.cfg.psi_hard_pct,
+        );
+        let held = self.reserve.held_chunks();
+        let target = policy::reserve_target_chunks(state, depth, self.reserve.total_chunks());
+        if held <= target {
+            return;
+        }
+
+        let released = self.reserve.deflate(held - target);
+        self.last_refill = None;
+        warn!(
+            avail_pct = %m.avail_pct(),
+            psi_avg10 = ?psi.map(|p| p.some_avg10),
+            depth = format!("{:.2}", depth),
+            released,
+            held = self.reserve.held_chunks(),
+            total = self.reserve.total_chunks(),
+            "released reserve chunks"
+        );
+        self.record(Decision::new(
+            state,
+            "release_reserve",
+            format!(
+                "{}; depth {:.2}, released {} of {} chunks",
+                pressure_reason(m.avail_pct(), psi, self.swap, self.exhaustion),
+                depth,
+                released,
+                self.reserve.total_chunks()
+            ),
+        ));
+    }
+
+    /* Re-inflate one chunk at a time, only while there is clear headroom
+     * above the soft threshold even after taking it back */
+    fn refill_reserve(&mut self, state: PressureState, m: &mem::MemInfo) {
+        if self.reserve.is_full() || m.mem_total == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(self.cfg.reserve_refill_interval_sec);
+        if self.last_refill.is_some_and(|t| t.elapsed() < interval) {
+            return;
+        }
+        let chunk_pct = self.reserve.chunk_bytes() as f64 / (m.mem_total * 1024) as f64 * 100.0;
+        let after = m.avail_pct() - chunk_pct;
+        if after <= self.cfg.soft_threshold_pct as f64 + self.cfg.reserve_refill_margin_pct {
+            return;
+        }
+
+        self.last_refill = Some(Instant::now());
+        match self.reserve.inflate(1) {
+            Ok(_) => {
+                info!(
+                    avail_pct = %m.avail_pct(),
+                    held = self.reserve.held_chunks(),
+                    total = self.reserve.total_chunks(),
+                    locked = self.reserve.is_locked(),
+                    "re-inflated reserve chunk"
+                );
+                self.record(Decision::new(
+                    state,
+                    "hold_reserve",
+                    format!(
+                        "avail_pct {:.1} above soft threshold; {}/{} chunks held",
+                        m.avail_pct(),
+                        self.reserve.held_chunks(),
+                        self.reserve.total_chunks()
+                    ),
+                ));
+            }
+            Err(e) => error!(error = %e, "failed to re-inflate reserve chunk"),
+        }
+    }
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
 
@@ -34,6 +769,16 @@ fn main() -> Result<()> {
         return stop_daemon();
     }
 
+    // Checked before forking so a bad config fails where it can be seen;
+    // defaults only stand in for a missing file
+    let cfg_path = PathBuf::from("/etc/memsentinel.toml");
+    let cfg_found = cfg_path.exists();
+    let mut cfg = if cfg_found {
+        Config::load_from(&cfg_path).with_context(|| format!("invalid config {:?}; refusing to start", cfg_path))?
+    } else {
+        Config::default()
+    };
+
     if args.silent {
         daemonize()?;
     }
@@ -46,9 +791,11 @@ fn main() -> Result<()> {
 
     info!("sentinel starting");
 
-    let cfg_path = PathBuf::from("/etc/memsentinel.toml");
-    let mut cfg = Config::load_from(&cfg_path).unwrap_or_default();
-    info!(?cfg_path, "loaded config or default");
+    if cfg_found {
+        info!(?cfg_path, "loaded config");
+    } else {
+        warn!(?cfg_path, "no config file; using defaults");
+    }
     
     let psi_available = PSIMetrics::is_available();
     if cfg.psi_enabled && !psi_available {
@@ -58,11 +805,32 @@ fn main() -> Result<()> {
     if psi_available {
         info!(psi_enabled = cfg.psi_enabled, "PSI support detected");
     }
+    let hierarchy = cgroups::hierarchy();
+    match hierarchy.limitations() {
+        Some(what) => warn!(%hierarchy, "{}", what),
+        None => info!(%hierarchy, "cgroup v2 detected"),
+    }
 
-    if !reserve::is_held() {
-        reserve::hold(cfg.reserve_mb);
-        info!(size_mb = cfg.reserve_mb, "reserve held");
+    let (event_tx, event_rx) = mpsc::channel();
+    let control_listening = match control::spawn_listener(Path::new(ctl::SOCKET_PATH), event_tx.clone()) {
+        Ok(()) => {
+            info!(socket = ctl::SOCKET_PATH, "control socket listening");
+            true
+        }
+        Err(e) => {
+            warn!(error = %e, "control socket unavailable; sentinelctl will fall back to local views");
+            false
+        }
+    };
+    if cfg.psi_enabled && cfg.psi_trigger_enabled {
+        trigger::spawn_psi_trigger(
+            Duration::from_millis(cfg.psi_trigger_stall_ms),
+            Duration::from_millis(cfg.psi_trigger_window_ms),
+            event_tx.clone(),
+        );
     }
+    // Only the listener and trigger threads keep the channel open
+    drop(event_tx);
 
     let term = Arc::new(AtomicBool::new(false));
     let hup = Arc::new(AtomicBool::new(false));
@@ -81,20 +849,41 @@ fn main() -> Result<()> {
         });
     }
 
+    let mut rt = Runtime {
+        cfg_path,
+        started: Instant::now(),
+        machine: PressureMachine::new(dwell(&cfg)),
+        avail_pct: 100.0,
+        psi_some_avg10: None,
+        psi_full_avg10: None,
+        swap: None,
+        trend: Trend::new(Duration::from_secs(cfg.trend_window_sec)),
+        exhaustion: None,
+        last_transition: None,
+        last_decision: None,
+        reserve: Reserve::new(cfg.reserve_mb, cfg.reserve_chunks, cfg.reserve_mlock),
+        last_refill: None,
+        frozen: FrozenSet::new(),
+        throttle: ThrottleSet::new(),
+        limiter: ActionLimiter::new(cfg.max_actions_per_min, cfg.emergency_max_actions_per_min),
+        ladder: Ladder::new(ladder::rungs_for(&cfg)),
+        exclude: Exclusions::new(&cfg.exclude_names)?,
+        rules: RuleSet::new(&cfg.rules)?,
+        protected: ProtectedUnits::new(&cfg.protected_units)?,
+        classes: SliceClasses::new(&cfg.slice_classes)?,
+        cfg,
+    };
+    warn_config(&rt.cfg);
+    rt.hold_reserve("startup");
+
     loop {
         if term.load(Ordering::SeqCst) {
             info!("terminating");
             break;
         }
         if hup.load(Ordering::SeqCst) {
-            match Config::load_from(&cfg_path) {
-                Ok(newc) => {
-                    cfg = newc;
-                    info!("reloaded config");
-                }
-                Err(e) => {
-                    warn!(error = %e, "failed to reload config; keeping previous");
-                }
+            if let Err(e) = rt.reload() {
+                warn!(error = format!("{:#}", e), "failed to reload config; keeping previous");
             }
             hup.store(false, Ordering::SeqCst);
         }
@@ -104,101 +893,193 @@ fn main() -> Result<()> {
             Err(e) => { error!(error=%e, "meminfo read error"); std::thread::sleep(Duration::from_secs(1)); continue; }
         };
         
-        let psi_metrics = if cfg.psi_enabled {
+        let psi_metrics = if rt.cfg.psi_enabled {
             PSIMetrics::sample().ok()
         } else {
             None
         };
         
-        let state = policy::classify_with_psi(
-            m.avail_pct,
-            cfg.soft_threshold_pct,
-            cfg.hard_threshold_pct,
-            psi_metrics.as_ref(),
-            cfg.psi_soft_pct,
-            cfg.psi_hard_pct,
-        );
+        // zram SwapFree is only as good as the RAM left to hold it
+        let comp = CompressedSwap::sample();
+        let swap_free_pct = comp.effective_swap_free_pct(&m);
+        let swap = swap_free_pct.map(|free_pct| SwapLevel {
+            free_pct,
+            soft: rt.cfg.swap_soft_threshold_pct,
+            hard: rt.cfg.swap_hard_threshold_pct,
+            hard_needs_mem: rt.cfg.swap_hard_needs_mem_pressure,
+        });
 
-        match state {
-            PressureState::Healthy => {
-                if !reserve::is_held() && m.avail_pct > (cfg.soft_threshold_pct as f64 + 5.0) {
-                    reserve::hold(cfg.reserve_mb);
-                    info!("reserve re-held");
-                }
+        let mut enter = rt.classify(&m, psi_metrics.as_ref(), swap_free_pct, false);
+        let mut exit = rt.classify(&m, psi_metrics.as_ref(), swap_free_pct, true);
+
+        // The reserve is headroom we can hand back, so releasing or refilling
+        // it must not look like a trend
+        let headroom_kb = m.available() + comp.effective_swap_free_kb(&m) + rt.reserve.size_bytes() / 1024;
+        rt.trend.push(headroom_kb);
+        rt.exhaustion = None;
+        if rt.cfg.trend_enabled {
+            let tte = rt.trend.time_to_exhaustion();
+            let predicted = policy::classify_trend(
+                tte,
+                Duration::from_secs(rt.cfg.trend_soft_horizon_sec),
+                Duration::from_secs(rt.cfg.trend_hard_horizon_sec),
+                enter,
+            );
+            if predicted > PressureState::Healthy {
+                rt.exhaustion = tte;
             }
-            PressureState::Soft => {
-                if reserve::is_held() {
-                    reserve::release();
-                    if let Some(ref psi) = psi_metrics {
-                        warn!(
-                            avail_pct = %m.avail_pct,
-                            psi_avg10 = %psi.some_avg10,
-                            "soft pressure: released reserve"
-                        );
-                    } else {
-                        warn!(avail_pct = %m.avail_pct, "soft pressure: released reserve");
-                    }
-                }
+            if predicted > enter {
+                warn!(
+                    exhaustion_secs = tte.map(|t| t.as_secs()),
+                    rate_mb_s = %format!("{:.1}", rt.trend.rate_kb_per_sec().unwrap_or(0.0) / 1024.0),
+                    from = ?enter,
+                    to = ?predicted,
+                    "memory running out fast; acting pre-emptively"
+                );
+                enter = predicted;
             }
-            PressureState::Hard => {
-                if reserve::is_held() {
-                    reserve::release();
-                }
-                
-                if let Some(ref psi) = psi_metrics {
-                    warn!(
-                        avail_pct = %m.avail_pct,
-                        psi_avg10 = %psi.some_avg10,
-                        psi_full_avg10 = %psi.full_avg10,
-                        "hard pressure detected"
-                    );
-                } else {
-                    warn!(avail_pct = %m.avail_pct, "hard pressure detected");
-                }
-                
-                if cfg.mode != "watch" {
-                    match procinfo::processes_with_badness(
-                        &cfg.exclude_names,
-                        &cfg.protected_units,
-                        m.total_kb * 1024,
-                    ) {
-                        Ok(procs) => {
-                            if let Some(victim) = procs.first() {
-                                info!(
-                                    pid = victim.pid,
-                                    name = %victim.name,
-                                    rss_mb = victim.rss_bytes / (1024*1024),
-                                    badness = %victim.badness_score,
-                                    slice = ?victim.cgroup_slice,
-                                    unit = ?victim.cgroup_unit,
-                                    "selected target for action"
-                                );
-                                
-                                if cfg.mode == "kill" || cfg.mode == "hybrid" {
-                                    if let Err(e) = actions::kill_process(victim.pid) {
-                                        error!(error = %e, "failed to kill process");
-                                    } else {
-                                        info!(pid = victim.pid, "killed process");
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!(error = %e, "failed to enumerate processes");
-                        }
-                    }
-                }
+            exit = exit.max(predicted);
+        }
+
+        let cause = pressure_reason(m.avail_pct(), psi_metrics.as_ref(), swap, rt.exhaustion);
+        if let Some(t) = rt.machine.update(enter, exit, &cause) {
+            if t.to > t.from {
+                warn!(from = ?t.from, to = ?t.to, held_secs = t.held_secs, cause = %t.cause, "pressure state changed");
+            } else {
+                info!(from = ?t.from, to = ?t.to, held_secs = t.held_secs, cause = %t.cause, "pressure state changed");
+            }
+            rt.last_transition = Some(t);
+        }
+        let state = rt.machine.state();
+        rt.avail_pct = m.avail_pct();
+        rt.psi_some_avg10 = psi_metrics.as_ref().map(|p| p.some_avg10);
+        rt.psi_full_avg10 = psi_metrics.as_ref().map(|p| p.full_avg10);
+        rt.swap = swap;
+
+        for gone in rt.frozen.prune() {
+            info!(pid = gone.pid, name = %gone.name, "frozen process exited");
+        }
+
+        if state == PressureState::Hard {
+            if let Some(ref psi) = psi_metrics {
+                warn!(
+                    avail_pct = %m.avail_pct(),
+                    psi_avg10 = %psi.some_avg10,
+                    psi_full_avg10 = %psi.full_avg10,
+                    "hard pressure detected"
+                );
+            } else {
+                warn!(avail_pct = %m.avail_pct(), "hard pressure detected");
+            }
+            if let Some(s) = swap.filter(|s| s.is_exhausted()) {
+                warn!(swap_free_pct = %format!("{:.1}", s.free_pct), "swap exhausted");
+            }
+        }
+
+        let steps = rt.ladder.step(state);
+        if state == PressureState::Healthy {
+            rt.refill_reserve(state, &m);
+            if !rt.throttle.is_empty() {
+                rt.restore_throttles();
+            }
+            rt.thaw_one(state);
+        } else {
+            // Once released, the reserve keeps following pressure depth
+            if rt.ladder.reached(LadderAction::ReleaseReserve) {
+                rt.deflate_reserve(state, &m, psi_metrics.as_ref());
+            }
+            for i in steps {
+                let rung = rt.ladder.rungs()[i].clone();
+                info!(rung = i + 1, action = %rung.action, state = ?state, timeout_sec = rung.timeout_sec,
+                      "escalation ladder step");
+                rt.run_rung(&rung, state, m.mem_total * 1024, &cause);
             }
         }
 
-        std::thread::sleep(Duration::from_secs(cfg.scan_interval_sec));
+        wait_for_next_tick(&mut rt, &event_rx);
+    }
+
+    rt.restore_throttles();
+
+    // Never leave processes stopped behind us
+    for (f, res) in rt.frozen.thaw_all() {
+        match res {
+            Ok(()) => info!(pid = f.pid, name = %f.name, "thawed process on exit"),
+            Err(e) => error!(pid = f.pid, error = %e, "failed to thaw process on exit"),
+        }
     }
 
+    if control_listening {
+        let _ = fs::remove_file(ctl::SOCKET_PATH);
+    }
     let _ = fs::remove_file(PID_FILE);
 
     Ok(())
 }
 
+fn warn_config(cfg: &Config) {
+    for w in cfg.warnings() {
+        warn!("{}", w);
+    }
+}
+
+fn dwell(cfg: &Config) -> Dwell {
+    Dwell {
+        soft: Duration::from_secs(cfg.dwell_soft_sec),
+        hard: Duration::from_secs(cfg.dwell_hard_sec),
+    }
+}
+
+fn pressure_reason(
+    avail_pct: f64,
+    psi: Option<&PSIMetrics>,
+    swap: Option<SwapLevel>,
+    exhaustion: Option<Duration>,
+) -> String {
+    let mut reason = match psi {
+        Some(p) => format!("avail_pct {:.1}, psi some_avg10 {:.2}", avail_pct, p.some_avg10),
+        None => format!("avail_pct {:.1}", avail_pct),
+    };
+    match swap {
+        Some(s) if s.is_exhausted() => reason.push_str(&format!(", swap exhausted ({:.1}% free)", s.free_pct)),
+        Some(s) if s.state() == PressureState::Soft => reason.push_str(&format!(", swap {:.1}% free", s.free_pct)),
+        _ => {}
+    }
+    if let Some(t) = exhaustion {
+        reason.push_str(&format!(", exhaustion predicted in {}s", t.as_secs()));
+    }
+    reason
+}
+
+/* Sleep until the next scan, answering control requests as they arrive.
+ * A PSI trigger cuts the sleep short so a fast allocation burst is seen
+ * straight away. */
+fn wait_for_next_tick(rt: &mut Runtime, rx: &Receiver<Event>) {
+    let interval = Duration::from_secs(rt.cfg.scan_interval_sec);
+    let deadline = Instant::now() + interval;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        match rx.recv_timeout(remaining) {
+            Ok(Event::Control(msg)) => {
+                let response = rt.handle(msg.request);
+                let _ = msg.reply.send(response);
+            }
+            Ok(Event::PsiTrigger) => {
+                debug!("PSI trigger fired; sampling early");
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => {
+                std::thread::sleep(remaining);
+                break;
+            }
+        }
+    }
+}
+
 fn daemonize() -> Result<()> {
     if let Ok(pid_str) = fs::read_to_string(PID_FILE) {
         if let Ok(pid) = pid_str.trim().parse::<i32>() {
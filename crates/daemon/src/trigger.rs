@@ -0,0 +1,49 @@
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use sentinel_core::psi::PsiTrigger;
+
+use crate::Event;
+
+/* Arm a kernel PSI trigger and wake the main loop each time it fires.
+ * If the kernel refuses it the scan timer still runs. */
+pub fn spawn_psi_trigger(stall: Duration, window: Duration, tx: Sender<Event>) {
+    let trigger = match PsiTrigger::new("some", stall, window).or_else(|e| unprivileged_retry(stall, window).ok_or(e)) {
+        Ok(t) => t,
+        Err(e) => {
+            warn!(error = %e, "PSI trigger unavailable; relying on scan interval");
+            return;
+        }
+    };
+    info!(trigger = trigger.as_str(), "PSI trigger armed");
+
+    std::thread::spawn(move || loop {
+        match trigger.wait(None) {
+            Ok(true) => {
+                if tx.send(Event::PsiTrigger).is_err() {
+                    break;
+                }
+            }
+            Ok(false) => {}
+            Err(e) => {
+                warn!(error = %e, "PSI trigger failed; relying on scan interval");
+                break;
+            }
+        }
+    });
+}
+
+/* Without CAP_SYS_RESOURCE the kernel only accepts windows that are a
+ * multiple of 2s; stretch the window and keep the same stall ratio. */
+fn unprivileged_retry(stall: Duration, window: Duration) -> Option<PsiTrigger> {
+    let step = Duration::from_secs(2).as_micros();
+    let micros = window.as_micros().div_ceil(step) * step;
+    if micros == window.as_micros() {
+        return None;
+    }
+    let scaled = Duration::from_micros((stall.as_micros() * micros / window.as_micros()) as u64);
+    let trigger = PsiTrigger::new("some", scaled, Duration::from_micros(micros as u64)).ok()?;
+    info!(requested_window_ms = window.as_millis() as u64, "PSI trigger window rounded up to a 2s multiple (no CAP_SYS_RESOURCE)");
+    Some(trigger)
+}